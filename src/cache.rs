@@ -0,0 +1,38 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Mutex;
+
+//
+
+type CacheKey = (String, String, String);
+
+// Formatted report texts keyed by (server, report, date).
+#[derive(Clone, Default)]
+pub struct ReportCache(Arc<Mutex<HashMap<CacheKey, (String, Instant)>>>);
+
+impl ReportCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn get(&self, key: &CacheKey, ttl: Duration) -> Option<String> {
+        let mut entries = self.0.lock().await;
+
+        match entries.get(key) {
+            Some((text, created)) if created.elapsed() < ttl => Some(text.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub async fn insert(&self, key: CacheKey, text: String) {
+        self.0.lock().await.insert(key, (text, Instant::now()));
+    }
+}
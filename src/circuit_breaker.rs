@@ -0,0 +1,123 @@
+use std::time::{Duration, Instant};
+
+//
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CircuitState {
+    Closed,
+    Open(Instant),
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub fn to_emoji(self) -> &'static str {
+        match self {
+            Self::Closed => "🟢",
+            Self::Open(_) => "🔴",
+            Self::HalfOpen => "🟡",
+        }
+    }
+}
+
+/// Stops calling a server after repeated failures and lets a single request
+/// through once `open_for` has passed
+pub struct CircuitBreaker {
+    state: CircuitState,
+    failures: u32,
+    threshold: u32,
+    open_for: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, open_for: Duration) -> Self {
+        Self {
+            state: CircuitState::Closed,
+            failures: 0,
+            threshold: threshold.max(1),
+            open_for,
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        match self.state {
+            CircuitState::Open(since) if since.elapsed() >= self.open_for => CircuitState::HalfOpen,
+            state => state,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        matches!(self.state(), CircuitState::Open(_))
+    }
+
+    pub fn record_success(&mut self) {
+        self.state = CircuitState::Closed;
+        self.failures = 0;
+    }
+
+    pub fn record_failure(&mut self) -> bool {
+        self.failures += 1;
+
+        let should_open = match self.state() {
+            CircuitState::Closed => self.failures >= self.threshold,
+            CircuitState::HalfOpen => true,
+            CircuitState::Open(_) => false,
+        };
+
+        if should_open {
+            self.state = CircuitState::Open(Instant::now());
+        }
+
+        should_open
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_threshold_failures() {
+        let mut breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+
+        assert!(!breaker.record_failure());
+        assert!(!breaker.record_failure());
+        assert!(!breaker.is_open());
+
+        assert!(breaker.record_failure());
+        assert!(breaker.is_open());
+    }
+
+    #[test]
+    fn success_resets_the_failure_count() {
+        let mut breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+
+        breaker.record_failure();
+        breaker.record_success();
+
+        assert!(!breaker.record_failure());
+        assert!(breaker.state() == CircuitState::Closed);
+    }
+
+    #[test]
+    fn half_opens_once_the_timeout_passes() {
+        let mut breaker = CircuitBreaker::new(1, Duration::ZERO);
+
+        breaker.record_failure();
+
+        assert!(breaker.state() == CircuitState::HalfOpen);
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn half_open_closes_on_success_and_reopens_on_failure() {
+        let mut breaker = CircuitBreaker::new(5, Duration::ZERO);
+
+        for _ in 0..5 {
+            breaker.record_failure();
+        }
+        assert!(breaker.record_failure());
+
+        breaker.record_success();
+        assert!(breaker.state() == CircuitState::Closed);
+    }
+}
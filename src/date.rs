@@ -1,19 +1,32 @@
-use chrono::{Duration, FixedOffset, Utc};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday,
+};
 
-pub fn moscow_time() -> (String, i64) {
-    let offset = FixedOffset::east_opt(3 * 3600).unwrap();
+pub struct MoscowTime(DateTime<FixedOffset>);
 
-    let time_utc = Utc::now();
-    let time_moscow = time_utc.with_timezone(&offset);
+impl MoscowTime {
+    pub fn date_string(&self) -> String {
+        self.0.format("%Y-%m-%d").to_string()
+    }
 
-    (
-        time_moscow.format("%Y-%m-%d").to_string(),
-        time_moscow
-            .format("%d")
-            .to_string()
-            .parse::<i64>()
-            .unwrap_or(0),
-    )
+    #[allow(dead_code)]
+    pub fn day_of_month(&self) -> i64 {
+        self.0.day() as i64
+    }
+
+    pub fn time_string(&self) -> String {
+        self.0.format("%H:%M").to_string()
+    }
+
+    pub fn as_datetime(&self) -> &DateTime<FixedOffset> {
+        &self.0
+    }
+}
+
+pub fn moscow_time() -> MoscowTime {
+    let offset = FixedOffset::east_opt(3 * 3600).unwrap();
+
+    MoscowTime(Utc::now().with_timezone(&offset))
 }
 
 pub fn moscow_last_(days: i64) -> String {
@@ -27,3 +40,246 @@ pub fn moscow_last_(days: i64) -> String {
         .unwrap();
     past.format("%Y-%m-%d").to_string()
 }
+
+fn start_of_month(date: NaiveDate) -> NaiveDate {
+    NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap()
+}
+
+pub fn moscow_start_of_month() -> String {
+    start_of_month(moscow_time().as_datetime().date_naive()).to_string()
+}
+
+fn last_month(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let last_day = start_of_month(today) - Duration::days(1);
+    let first_day = last_day - Duration::days(last_day.day() as i64 - 1);
+
+    (first_day, last_day)
+}
+
+/// First and last day of the previous calendar month
+pub fn moscow_last_month() -> (String, String) {
+    let (first_day, last_day) = last_month(moscow_time().as_datetime().date_naive());
+
+    (first_day.to_string(), last_day.to_string())
+}
+
+pub fn moscow_current_quarter() -> u8 {
+    moscow_time().as_datetime().month0() as u8 / 3 + 1
+}
+
+/// Start of the given quarter of the current year and its last day, capped at
+/// today; `None` for quarters outside 1..=4 or still in the future
+pub fn moscow_quarter(quarter: u8) -> Option<(String, String)> {
+    let (first_day, last_day) = quarter_range(quarter, moscow_time().as_datetime().date_naive())?;
+
+    Some((first_day.to_string(), last_day.to_string()))
+}
+
+fn quarter_range(quarter: u8, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    if !(1..=4).contains(&quarter) {
+        return None;
+    }
+
+    let first_month = (quarter as u32 - 1) * 3 + 1;
+    let first_day = NaiveDate::from_ymd_opt(today.year(), first_month, 1)?;
+
+    if first_day > today {
+        return None;
+    }
+
+    let next_quarter = match quarter {
+        4 => NaiveDate::from_ymd_opt(today.year() + 1, 1, 1)?,
+        _ => NaiveDate::from_ymd_opt(today.year(), first_month + 3, 1)?,
+    };
+
+    let last_day = (next_quarter - Duration::days(1)).min(today);
+
+    Some((first_day, last_day))
+}
+
+pub fn moscow_now() -> NaiveDateTime {
+    moscow_time().as_datetime().naive_local()
+}
+
+pub fn until_next_moscow(time: NaiveTime) -> std::time::Duration {
+    let now = moscow_now();
+
+    let mut next = now.date().and_time(time);
+
+    if next <= now {
+        next += Duration::days(1);
+    }
+
+    (next - now).to_std().unwrap_or_default()
+}
+
+fn weekday_short(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Пн",
+        Weekday::Tue => "Вт",
+        Weekday::Wed => "Ср",
+        Weekday::Thu => "Чт",
+        Weekday::Fri => "Пт",
+        Weekday::Sat => "Сб",
+        Weekday::Sun => "Вс",
+    }
+}
+
+/// "Вс, 14.01.2024"
+pub fn format_day(date: NaiveDate) -> String {
+    format!(
+        "{}, {}",
+        weekday_short(date.weekday()),
+        date.format("%d.%m.%Y")
+    )
+}
+
+pub fn format_duration(secs: i64) -> String {
+    let minutes = secs.max(0) / 60;
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+
+    if days > 0 {
+        format!("{}д {}ч", days, hours)
+    } else if hours > 0 {
+        format!("{}ч {}мин", hours, minutes)
+    } else {
+        format!("{}мин", minutes)
+    }
+}
+
+pub fn parse_date_input(s: &str) -> Result<String, String> {
+    let input = s.trim();
+
+    // %Y would read "24" as the year 24, so the two-digit year goes first
+    for format in ["%Y-%m-%d", "%d.%m.%y", "%d.%m.%Y", "%d/%m/%Y"] {
+        if let Ok(date) = NaiveDate::parse_from_str(input, format) {
+            return Ok(date.format("%Y-%m-%d").to_string());
+        }
+    }
+
+    Err(format!(
+        "Не удалось распознать дату '{}'. Пример: 2024-01-01 или 01.01.2024",
+        input
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn parse_date_input_normalizes_supported_formats() {
+        for input in [
+            "2024-01-05",
+            "05.01.2024",
+            "05.01.24",
+            "05/01/2024",
+            " 2024-01-05\n",
+        ] {
+            assert_eq!(
+                parse_date_input(input),
+                Ok("2024-01-05".to_string()),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_date_input_rejects_invalid_dates() {
+        for input in ["", "завтра", "2024-02-30", "32.01.2024", "2024/01/05"] {
+            assert!(parse_date_input(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn format_duration_picks_units_by_magnitude() {
+        assert_eq!(format_duration(45 * 60), "45мин");
+        assert_eq!(format_duration(2 * 3600 + 15 * 60), "2ч 15мин");
+        assert_eq!(format_duration(3 * 86400 + 5 * 3600 + 59), "3д 5ч");
+    }
+
+    #[test]
+    fn format_duration_clamps_negative_durations() {
+        assert_eq!(format_duration(-120), "0мин");
+    }
+
+    #[test]
+    fn start_of_month_is_the_first_on_any_day() {
+        for day in [1, 2, 15, 31] {
+            assert_eq!(start_of_month(date(2024, 1, day)), date(2024, 1, 1));
+        }
+        assert_eq!(start_of_month(date(2024, 2, 29)), date(2024, 2, 1));
+    }
+
+    #[test]
+    fn moscow_start_of_month_is_in_the_current_month() {
+        let today = moscow_time().date_string();
+
+        assert_eq!(moscow_start_of_month(), format!("{}-01", &today[..7]));
+    }
+
+    #[test]
+    fn last_month_in_the_middle_of_the_year() {
+        assert_eq!(
+            last_month(date(2024, 3, 15)),
+            (date(2024, 2, 1), date(2024, 2, 29))
+        );
+    }
+
+    #[test]
+    fn last_month_in_january_is_the_previous_december() {
+        assert_eq!(
+            last_month(date(2024, 1, 1)),
+            (date(2023, 12, 1), date(2023, 12, 31))
+        );
+        assert_eq!(
+            last_month(date(2024, 1, 31)),
+            (date(2023, 12, 1), date(2023, 12, 31))
+        );
+    }
+
+    #[test]
+    fn quarter_range_of_a_past_quarter_is_the_whole_quarter() {
+        let today = date(2024, 11, 20);
+
+        assert_eq!(
+            quarter_range(1, today),
+            Some((date(2024, 1, 1), date(2024, 3, 31)))
+        );
+        assert_eq!(
+            quarter_range(3, today),
+            Some((date(2024, 7, 1), date(2024, 9, 30)))
+        );
+    }
+
+    #[test]
+    fn quarter_range_of_the_current_quarter_ends_today() {
+        assert_eq!(
+            quarter_range(4, date(2024, 11, 20)),
+            Some((date(2024, 10, 1), date(2024, 11, 20)))
+        );
+    }
+
+    #[test]
+    fn quarter_range_rejects_invalid_and_future_quarters() {
+        let today = date(2024, 5, 1);
+
+        assert_eq!(quarter_range(0, today), None);
+        assert_eq!(quarter_range(5, today), None);
+        assert_eq!(quarter_range(3, today), None);
+    }
+
+    #[test]
+    fn day_of_month_matches_the_date_string() {
+        let now = moscow_time();
+
+        assert_eq!(
+            now.day_of_month().to_string(),
+            now.0.format("%-d").to_string()
+        );
+    }
+}
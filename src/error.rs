@@ -0,0 +1,91 @@
+use std::{error::Error, fmt::Display};
+
+use teloxide::{RequestError, dispatching::dialogue::InMemStorageError};
+
+//
+
+#[derive(Debug)]
+pub enum BotError {
+    IikoApi(String),
+    Telegram(RequestError),
+    Config(String),
+    Auth(String),
+    NotFound(String),
+    Unauthorized,
+    Timeout,
+    InvalidDateRange,
+}
+
+impl Display for BotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IikoApi(e) => write!(f, "Ошибка iiko API: {e}"),
+            Self::Telegram(e) => write!(f, "Ошибка Telegram: {e}"),
+            Self::Config(e) => write!(f, "Ошибка конфигурации: {e}"),
+            Self::Auth(e) => write!(f, "Ошибка авторизации: {e}"),
+            Self::NotFound(e) => write!(f, "{e}"),
+            Self::Unauthorized => write!(f, "Пользователь не авторизован"),
+            Self::Timeout => write!(f, "Превышено время ожидания ответа"),
+            Self::InvalidDateRange => write!(f, "Некорректный диапазон дат"),
+        }
+    }
+}
+
+impl Error for BotError {}
+
+//
+
+impl From<reqwest::Error> for BotError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout
+        } else {
+            Self::IikoApi(e.to_string())
+        }
+    }
+}
+
+impl From<reqwest_middleware::Error> for BotError {
+    fn from(e: reqwest_middleware::Error) -> Self {
+        match e {
+            reqwest_middleware::Error::Reqwest(e) => e.into(),
+            reqwest_middleware::Error::Middleware(e) => Self::IikoApi(e.to_string()),
+        }
+    }
+}
+
+impl From<RequestError> for BotError {
+    fn from(e: RequestError) -> Self {
+        Self::Telegram(e)
+    }
+}
+
+impl From<InMemStorageError> for BotError {
+    fn from(e: InMemStorageError) -> Self {
+        Self::NotFound(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for BotError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Config(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for BotError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::IikoApi(e.to_string())
+    }
+}
+
+impl From<toml::de::Error> for BotError {
+    fn from(e: toml::de::Error) -> Self {
+        Self::Config(e.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for BotError {
+    fn from(e: toml::ser::Error) -> Self {
+        Self::Config(e.to_string())
+    }
+}
@@ -0,0 +1,80 @@
+use csv::Writer;
+
+use crate::iiko::Shifts;
+
+//
+
+const HEADERS: [&str; 23] = [
+    "ID",
+    "Номер смены",
+    "Фискальный номер",
+    "Номер кассы",
+    "Серийный номер кассы",
+    "Дата открытия",
+    "Дата закрытия",
+    "Дата принятия",
+    "ID менеджера",
+    "ID ответственного",
+    "Наличные на начало смены",
+    "Сумма заказов",
+    "Сумма списаний",
+    "Продажи наличными",
+    "Продажи в кредит",
+    "Продажи картой",
+    "Внесения",
+    "Изъятия",
+    "Доход",
+    "Остаток наличных",
+    "Расхождение наличных",
+    "Статус",
+    "ID концепции",
+];
+
+pub fn shifts_to_csv(shifts: &Shifts) -> String {
+    let mut writer = Writer::from_writer(Vec::new());
+
+    writer
+        .write_record(HEADERS)
+        .expect("writing CSV to memory can't fail");
+
+    for shift in shifts {
+        let record = [
+            shift.id.clone(),
+            shift.session_number.to_string(),
+            shift.fiscal_number.to_string(),
+            shift.cash_reg_number.to_string(),
+            shift.cash_reg_serial.clone(),
+            shift.open_date.clone(),
+            shift.close_date.clone().unwrap_or_default(),
+            shift.accept_date.clone().unwrap_or_default(),
+            shift.manager_id.clone(),
+            shift.responsible_user_id.clone().unwrap_or_default(),
+            shift.session_start_cash.to_string(),
+            shift.pay_orders.to_string(),
+            shift.sum_writeoff_orders.to_string(),
+            shift.sales_cash.to_string(),
+            shift.sales_credit.to_string(),
+            shift.sales_card.to_string(),
+            shift.pay_in.to_string(),
+            shift.pay_out.to_string(),
+            shift.pay_income.to_string(),
+            shift
+                .cash_remain
+                .map(|cash| cash.to_string())
+                .unwrap_or_default(),
+            shift.cash_diff.to_string(),
+            shift.session_status.to_string(),
+            shift.conception_id.clone().unwrap_or_default(),
+        ];
+
+        writer
+            .write_record(&record)
+            .expect("writing CSV to memory can't fail");
+    }
+
+    let bytes = writer
+        .into_inner()
+        .expect("writing CSV to memory can't fail");
+
+    String::from_utf8(bytes).unwrap_or_default()
+}
@@ -0,0 +1,196 @@
+use std::sync::OnceLock;
+
+//
+
+static THOUSAND_SEPARATOR: OnceLock<char> = OnceLock::new();
+
+// Set once at startup from the config; everything formatted before that (or
+// without a config value) falls back to a non-breaking space.
+pub fn set_thousand_separator(separator: char) {
+    let _ = THOUSAND_SEPARATOR.set(separator);
+}
+
+fn thousand_separator() -> char {
+    THOUSAND_SEPARATOR.get().copied().unwrap_or('\u{00A0}')
+}
+
+fn group_thousands(digits: &str) -> String {
+    let separator = thousand_separator();
+    let length = digits.chars().count();
+    let mut result = String::with_capacity(length + length / 3 * separator.len_utf8());
+
+    for (size, character) in digits.chars().enumerate() {
+        let rem = length - size;
+
+        if size > 0 && rem.is_multiple_of(3) {
+            result.push(separator);
+        }
+
+        result.push(character);
+    }
+
+    result
+}
+
+//
+
+pub fn format_with_dots(number: usize) -> String {
+    group_thousands(&number.to_string())
+}
+
+// Formats a rouble amount the Russian way: thousand separators and a comma
+// before the kopecks, e.g. 1234567.5 -> "1 234 567,50".
+pub fn format_f64_with_dots(number: f64) -> String {
+    let kopecks = (number.abs() * 100.0).round() as u64;
+
+    let sign = if number < 0.0 && kopecks > 0 { "-" } else { "" };
+
+    format!(
+        "{}{},{:02}",
+        sign,
+        group_thousands(&(kopecks / 100).to_string()),
+        kopecks % 100
+    )
+}
+
+//
+
+static CURRENCY_SYMBOL: OnceLock<String> = OnceLock::new();
+
+pub fn set_currency_symbol(symbol: String) {
+    let _ = CURRENCY_SYMBOL.set(symbol);
+}
+
+pub fn format_ruble(amount: f64) -> String {
+    let symbol = CURRENCY_SYMBOL.get().map(String::as_str).unwrap_or("₽");
+
+    format!("{} {}", format_f64_with_dots(amount), symbol)
+}
+
+//
+
+// Splits a message on line boundaries so that every chunk fits into
+// `max_len` characters. A code block cut in two is closed at the end of the
+// first chunk and reopened at the start of the next one.
+pub fn split_message(text: &str, max_len: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut in_code_block = false;
+
+    for line in text.split('\n') {
+        let closing = if in_code_block { "\n```".len() } else { 0 };
+
+        if !current.is_empty()
+            && current.chars().count() + 1 + line.chars().count() + closing > max_len
+        {
+            if in_code_block {
+                current.push_str("\n```");
+            }
+
+            chunks.push(current);
+
+            current = if in_code_block {
+                "```".to_string()
+            } else {
+                String::new()
+            };
+        }
+
+        if !current.is_empty() {
+            current.push('\n');
+        }
+
+        current.push_str(line);
+
+        if line.matches("```").count() % 2 == 1 {
+            in_code_block = !in_code_block;
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+// For messages that can't be split, such as edits: keeps the lines that fit
+// into `max_len` characters and marks the cut with "…".
+pub fn clamp_message(text: &str, max_len: usize) -> String {
+    let mut chunks = split_message(text, max_len.saturating_sub("\n…".chars().count()));
+
+    if chunks.len() <= 1 {
+        return chunks.pop().unwrap_or_default();
+    }
+
+    let mut first = chunks.swap_remove(0);
+    first.push_str("\n…");
+    first
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_with_dots_groups_thousands_with_a_non_breaking_space() {
+        assert_eq!(format_with_dots(999), "999");
+        assert_eq!(format_with_dots(1000), "1\u{00A0}000");
+        assert_eq!(format_with_dots(1000000), "1\u{00A0}000\u{00A0}000");
+    }
+
+    #[test]
+    fn format_f64_with_dots_shows_kopecks() {
+        assert_eq!(
+            format_f64_with_dots(1234567.5),
+            "1\u{00A0}234\u{00A0}567,50"
+        );
+        assert_eq!(format_f64_with_dots(0.0), "0,00");
+    }
+
+    #[test]
+    fn format_f64_with_dots_rounds_to_kopecks() {
+        assert_eq!(format_f64_with_dots(0.125), "0,13");
+        assert_eq!(format_f64_with_dots(999.999), "1\u{00A0}000,00");
+    }
+
+    #[test]
+    fn format_f64_with_dots_keeps_the_sign_of_negatives() {
+        assert_eq!(format_f64_with_dots(-1500.25), "-1\u{00A0}500,25");
+        assert_eq!(format_f64_with_dots(-0.001), "0,00");
+    }
+
+    #[test]
+    fn split_message_keeps_short_text_whole() {
+        assert_eq!(split_message("one\ntwo", 100), vec!["one\ntwo"]);
+    }
+
+    #[test]
+    fn split_message_breaks_on_line_boundaries() {
+        assert_eq!(
+            split_message("aaaa\nbbbb\ncccc", 9),
+            vec!["aaaa\nbbbb", "cccc"]
+        );
+    }
+
+    #[test]
+    fn split_message_closes_and_reopens_code_blocks() {
+        let chunks = split_message("```\naaaa\nbbbb\ncccc\n```", 17);
+
+        assert_eq!(chunks, vec!["```\naaaa\nbbbb\n```", "```\ncccc\n```"]);
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 17));
+    }
+
+    #[test]
+    fn clamp_message_keeps_short_text_whole() {
+        assert_eq!(clamp_message("one\ntwo", 100), "one\ntwo");
+    }
+
+    #[test]
+    fn clamp_message_closes_the_code_block_and_marks_the_cut() {
+        let clamped = clamp_message("```\naaaa\nbbbb\ncccc\n```", 19);
+
+        assert_eq!(clamped, "```\naaaa\nbbbb\n```\n…");
+        assert!(clamped.chars().count() <= 19);
+    }
+}
@@ -0,0 +1,7 @@
+mod olap_api;
+mod server;
+mod shifts;
+
+pub use olap_api::Olap;
+pub use server::Server;
+pub use shifts::{Dates, GetShifts, Shifts, compare_shifts, display_shifts_table};
@@ -0,0 +1,314 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use reqwest::StatusCode;
+
+use serde_json::from_str;
+
+use crate::{
+    error::BotError,
+    olap::{
+        IikoApiError, OLAP_NO_DATA, OLAPList, OlapElement, OlapMap, SortOrder, olap_page_count,
+        wrap_text,
+    },
+    shared::make_url,
+};
+
+use super::server::{Server, ensure_json};
+
+//
+
+pub trait Olap {
+    async fn get_olap(form: String, server: &mut Server) -> Result<OlapMap, BotError>;
+
+    fn display_olap_page(
+        elements: &[OlapElement],
+        page: usize,
+        page_size: usize,
+        sort: SortOrder,
+    ) -> String;
+}
+
+impl Olap for Server {
+    async fn get_olap(form: String, server: &mut Server) -> Result<OlapMap, BotError> {
+        let url = make_url(
+            &server.server.url,
+            &["v2", "reports", "olap"],
+            server.server.use_https,
+        )?;
+
+        let key = server.get_token().await?;
+
+        server.check_circuit()?;
+
+        let mut result = server.post_json(&url, &form, &key).await;
+
+        // The token may expire between get_token and the request itself
+        if let Ok(response) = &result
+            && response.status() == StatusCode::UNAUTHORIZED
+        {
+            server.force_reauth().await?;
+
+            let key = server.get_token().await?;
+
+            result = server.post_json(&url, &form, &key).await;
+        }
+
+        server.record_response(&result);
+        let response = result?;
+
+        if response.status() == StatusCode::UNAUTHORIZED {
+            return Err(BotError::Auth("Re-authentication failed".into()));
+        }
+
+        let response = response.text().await?;
+
+        ensure_json(&response)?;
+
+        if let Ok(api_error) = from_str::<IikoApiError>(&response) {
+            tracing::warn!(error = api_error.error, "iiko rejected the OLAP request");
+            return Err(BotError::IikoApi(api_error.error_description));
+        }
+
+        let parsed: OLAPList = from_str(&response)?;
+
+        let mut olap_map: OlapMap = HashMap::new();
+
+        for element in parsed.data {
+            let key = element.DishCategory.unwrap_or_else(|| "Другие".into());
+            let olap = OlapElement {
+                DishDiscountSumInt: element.DishDiscountSumInt,
+                DishName: element.DishName,
+                GuestNum: element.GuestNum,
+            };
+            olap_map
+                .entry(key)
+                .and_modify(|v| v.push(olap.clone()))
+                .or_insert_with(|| vec![olap]);
+        }
+
+        Ok(olap_map)
+    }
+    fn display_olap_page(
+        elements: &[OlapElement],
+        page: usize,
+        page_size: usize,
+        sort: SortOrder,
+    ) -> String {
+        if elements.is_empty() {
+            return OLAP_NO_DATA.to_string();
+        }
+
+        let headers = ["Название", "Сумма", "Заказы"];
+
+        let mut sorted: Vec<&OlapElement> = elements.iter().collect();
+        match sort {
+            SortOrder::ByRevenue => {
+                sorted.sort_by(|a, b| b.DishDiscountSumInt.total_cmp(&a.DishDiscountSumInt))
+            }
+            SortOrder::ByGuestNum => sorted.sort_by_key(|element| Reverse(element.GuestNum)),
+            SortOrder::ByName => sorted.sort_by(|a, b| a.DishName.cmp(&b.DishName)),
+        }
+
+        let pages = olap_page_count(elements.len(), page_size);
+        let page = page.min(pages - 1);
+
+        let displayed = sorted
+            .into_iter()
+            .skip(page * page_size)
+            .take(page_size)
+            .collect::<Vec<_>>();
+
+        let mut widths = headers
+            .iter()
+            .map(|h| h.chars().count())
+            .collect::<Vec<usize>>();
+
+        let total_sum: f64 = elements.iter().map(|e| e.DishDiscountSumInt).sum();
+        let total_guests: u32 = elements.iter().map(|e| e.GuestNum).sum();
+
+        widths[1] = widths[1].max(total_sum.to_string().len());
+        widths[2] = widths[2].max(total_guests.to_string().len());
+
+        for element in &displayed {
+            widths[0] = widths[0].max(element.DishName.chars().count().min(15));
+            widths[1] = widths[1].max(element.DishDiscountSumInt.to_string().len());
+            widths[2] = widths[2].max(element.GuestNum.to_string().len());
+        }
+
+        let draw_border = |left: char, middle: char, separator: char, right: char| {
+            let mut string = String::new();
+            string.push(left);
+            for (i, &w) in widths.iter().enumerate() {
+                string.push_str(&middle.to_string().repeat(w + 2));
+                string.push(if i + 1 == widths.len() {
+                    right
+                } else {
+                    separator
+                });
+            }
+            string.push('\n');
+            string
+        };
+
+        let mut table = String::new();
+
+        table.push_str("```\n");
+        if pages > 1 {
+            table.push_str(&format!("Стр. {}/{}\n", page + 1, pages));
+        }
+        table.push_str(&draw_border('┌', '─', '┬', '┐'));
+        table.push('│');
+
+        for (i, &h) in headers.iter().enumerate() {
+            let total = widths[i] + 2;
+            let pad_left = (total - h.chars().count()) / 2;
+            let pad_right = total - h.chars().count() - pad_left;
+            table.push_str(&" ".repeat(pad_left));
+            table.push_str(h);
+            table.push_str(&" ".repeat(pad_right));
+            table.push('│');
+        }
+
+        table.push('\n');
+        table.push_str(&draw_border('├', '─', '┼', '┤'));
+
+        for element in &displayed {
+            let name_lines = wrap_text(&element.DishName, widths[0]);
+            for (line_idx, line) in name_lines.into_iter().enumerate() {
+                table.push('│');
+
+                let pad_right = widths[0] + 2 - 1 - line.chars().count();
+                table.push(' ');
+                table.push_str(&line);
+                table.push_str(&" ".repeat(pad_right));
+                table.push('│');
+
+                let fields = if line_idx == 0 {
+                    vec![
+                        element.DishDiscountSumInt.to_string(),
+                        element.GuestNum.to_string(),
+                    ]
+                } else {
+                    vec![String::new(), String::new()]
+                };
+                for (size, cell) in fields.iter().enumerate() {
+                    let total = widths[size + 1] + 2;
+                    let pad_right = total - 1 - cell.chars().count();
+
+                    table.push(' ');
+                    table.push_str(cell);
+                    table.push_str(&" ".repeat(pad_right));
+                    table.push('│');
+                }
+
+                table.push('\n');
+            }
+            table.push_str(&draw_border('├', '─', '┼', '┤'));
+        }
+
+        let total_label = "Итого";
+        table.push_str(&format!(
+            "│ {}{}│",
+            total_label,
+            " ".repeat(widths[0] + 1 - total_label.chars().count())
+        ));
+
+        for (size, cell) in [total_sum.to_string(), total_guests.to_string()]
+            .iter()
+            .enumerate()
+        {
+            let pad_right = widths[size + 1] + 1 - cell.chars().count();
+            table.push_str(&format!(" {}{}│", cell, " ".repeat(pad_right)));
+        }
+
+        table.push('\n');
+        table.push_str(&draw_border('└', '─', '┴', '┘'));
+        if elements.len() > page_size {
+            table.push_str(&format!(
+                "Показано {} из {} позиций\n",
+                displayed.len(),
+                elements.len()
+            ));
+        }
+        table.push_str("```\n");
+
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shared::ServerConfig;
+
+    use super::*;
+
+    fn elements(count: usize) -> Vec<OlapElement> {
+        (0..count)
+            .map(|i| OlapElement {
+                DishDiscountSumInt: 100.0,
+                DishName: format!("Блюдо {}", i),
+                GuestNum: 1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn display_olap_page_without_elements_is_the_no_data_message() {
+        assert_eq!(
+            Server::display_olap_page(&[], 0, 20, SortOrder::default()),
+            OLAP_NO_DATA
+        );
+    }
+
+    #[test]
+    fn display_olap_page_counts_shown_rows_only_when_truncated() {
+        let page = |count| Server::display_olap_page(&elements(count), 0, 20, SortOrder::default());
+
+        assert!(!page(1).contains("Показано"));
+        assert!(!page(20).contains("Показано"));
+        assert!(page(21).contains("Показано 20 из 21 позиций"));
+    }
+
+    async fn olap_with_response(body: &str) -> Result<OlapMap, BotError> {
+        let mut mock = mockito::Server::new_async().await;
+
+        mock.mock("GET", "/resto/api/auth")
+            .match_query(mockito::Matcher::Any)
+            .with_body("token")
+            .create_async()
+            .await;
+
+        mock.mock("POST", "/resto/api/v2/reports/olap")
+            .match_query(mockito::Matcher::Any)
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let mut server = Server::new(
+            "login",
+            "pass",
+            ServerConfig {
+                url: mock.host_with_port(),
+                use_https: false,
+            },
+        );
+
+        Server::get_olap("{}".into(), &mut server).await
+    }
+
+    #[tokio::test]
+    async fn get_olap_surfaces_plain_text_errors() {
+        match olap_with_response("Error: Invalid key").await {
+            Err(BotError::IikoApi(message)) => assert_eq!(message, "Error: Invalid key"),
+            _ => panic!("expected an iiko API error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_olap_surfaces_html_error_pages() {
+        match olap_with_response("<html><body>502 Bad Gateway</body></html>").await {
+            Err(BotError::IikoApi(message)) => assert!(message.contains("502 Bad Gateway")),
+            _ => panic!("expected an iiko API error"),
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use std::{
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use reqwest_retry::{RetryTransientMiddleware, policies::ExponentialBackoff};
+
+use crate::{
+    circuit_breaker::{CircuitBreaker, CircuitState},
+    error::BotError,
+    shared::{ServerConfig, make_url, sha256sum},
+};
+
+//
+
+pub struct Server {
+    login: String,
+    pass: String,
+    pub(super) server: ServerConfig,
+    pub(super) client: ClientWithMiddleware,
+    pub(super) token: Option<NewToken>,
+    refresh_margin: Duration,
+    hash_algo: String,
+    breaker: CircuitBreaker,
+    olap_timeout: Duration,
+}
+
+fn build_client(connect_timeout: Duration, read_timeout: Duration) -> ClientWithMiddleware {
+    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
+
+    let client = reqwest::Client::builder()
+        .connect_timeout(connect_timeout)
+        .timeout(read_timeout)
+        .build()
+        .unwrap_or_default();
+
+    ClientBuilder::new(client)
+        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+        .build()
+}
+
+impl Server {
+    pub fn new<S: Into<String>>(login: S, pass: S, server: ServerConfig) -> Self {
+        Self {
+            login: login.into(),
+            pass: pass.into(),
+            server,
+            client: build_client(Duration::from_secs(3), Duration::from_secs(30)),
+            token: None,
+            refresh_margin: Duration::from_secs(300),
+            hash_algo: "sha256".into(),
+            breaker: CircuitBreaker::new(5, Duration::from_secs(60)),
+            olap_timeout: Duration::from_secs(60),
+        }
+    }
+
+    pub fn with_refresh_margin(mut self, margin: Duration) -> Self {
+        self.refresh_margin = margin;
+        self
+    }
+
+    pub fn with_timeouts(mut self, connect: Duration, read: Duration) -> Self {
+        self.client = build_client(connect, read);
+        self
+    }
+
+    // OLAP reports are computed on request and can take far longer than the
+    // client's read timeout allows.
+    pub fn with_olap_timeout(mut self, timeout: Duration) -> Self {
+        self.olap_timeout = timeout;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, threshold: u32, open_for: Duration) -> Self {
+        self.breaker = CircuitBreaker::new(threshold, open_for);
+        self
+    }
+
+    pub fn circuit_state(&self) -> CircuitState {
+        self.breaker.state()
+    }
+
+    pub(super) fn check_circuit(&self) -> Result<(), BotError> {
+        if self.breaker.is_open() {
+            return Err(BotError::IikoApi(format!(
+                "сервер {} временно отключён после серии ошибок",
+                self.server
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn record_result<T>(&mut self, result: &Result<T, BotError>) {
+        match result {
+            Ok(_) => self.breaker.record_success(),
+            Err(_) => {
+                if self.breaker.record_failure() {
+                    tracing::warn!(server = %self.server, "circuit breaker opened");
+                }
+            }
+        }
+    }
+
+    /// Like `record_result`, but an error status from the server also counts as a failure
+    pub(super) fn record_response(&mut self, result: &Result<reqwest::Response, BotError>) {
+        match result {
+            Ok(response) if !response.status().is_success() => {
+                if self.breaker.record_failure() {
+                    tracing::warn!(server = %self.server, "circuit breaker opened");
+                }
+            }
+            result => self.record_result(result),
+        }
+    }
+
+    pub fn with_hash_algo<S: Into<String>>(mut self, algo: S) -> Self {
+        self.hash_algo = algo.into();
+        self
+    }
+
+    fn hashed_pass(&self) -> Result<String, BotError> {
+        match self.hash_algo.as_str() {
+            #[allow(deprecated)]
+            "sha1" => Ok(crate::shared::sha1sum(&self.pass)),
+            "sha256" => Ok(sha256sum(&self.pass)),
+            other => Err(BotError::Config(format!(
+                "Неизвестный алгоритм хэширования: {}",
+                other
+            ))),
+        }
+    }
+
+    pub fn expires_within(&self, margin: Duration) -> bool {
+        match &self.token {
+            Some(token) => token.will_expire_soon(margin),
+            None => false,
+        }
+    }
+
+    pub(super) async fn auth(&mut self) -> Result<(), BotError> {
+        let needs_refresh = match &self.token {
+            Some(token) => token.will_expire_soon(self.refresh_margin),
+            None => true,
+        };
+
+        if needs_refresh {
+            self.check_circuit()?;
+
+            let result = self.force_reauth().await;
+            self.record_result(&result);
+            result?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn force_reauth(&mut self) -> Result<(), BotError> {
+        let url = make_url(&self.server.url, &["auth"], self.server.use_https)?;
+
+        let pass = self.hashed_pass()?;
+
+        let response = self
+            .client
+            .get(&url)
+            .query(&[("login", &self.login), ("pass", &pass)])
+            .send()
+            .await?;
+
+        let status = response.status();
+        let token = response.text().await?;
+
+        if !status.is_success() {
+            return Err(BotError::Auth(token));
+        }
+
+        let token = NewToken {
+            id: token,
+            creation_time: Utc::now(),
+            lifetime: Duration::from_secs(3600),
+        };
+
+        self.token = Some(token);
+
+        Ok(())
+    }
+
+    pub async fn deauth(&mut self) -> Result<(), BotError> {
+        if self.is_authenticated() {
+            let url = make_url(&self.server.url, &["logout"], self.server.use_https)?;
+
+            self.client
+                .get(url)
+                .query(&[("key", self.token.clone().unwrap().id.clone())])
+                .send()
+                .await?
+                .text()
+                .await?;
+        }
+
+        self.token = None;
+        Ok(())
+    }
+
+    fn is_authenticated(&self) -> bool {
+        let token = self.token.as_ref();
+
+        if token.is_none() {
+            return false;
+        }
+
+        if token.unwrap().is_expired() {
+            return false;
+        }
+
+        true
+    }
+
+    pub async fn get_token(&mut self) -> Result<String, BotError> {
+        self.auth().await?;
+
+        Ok(self.token.clone().unwrap().id)
+    }
+
+    pub(super) async fn post_json(
+        &self,
+        url: &str,
+        body: &str,
+        key: &str,
+    ) -> Result<reqwest::Response, BotError> {
+        let response = self
+            .client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .query(&[("key", key)])
+            .timeout(self.olap_timeout)
+            .body(body.to_string())
+            .send()
+            .await?;
+
+        Ok(response)
+    }
+
+    pub async fn ping(server: &ServerConfig, timeout: Duration) -> Result<Duration, BotError> {
+        let url = make_url(&server.url, &["auth"], server.use_https)?;
+
+        let start = Instant::now();
+
+        reqwest::Client::new()
+            .get(url)
+            .query(&[("login", ""), ("pass", "")])
+            .timeout(timeout)
+            .send()
+            .await?;
+
+        Ok(start.elapsed())
+    }
+}
+
+// Credentials and the token itself are left out so the server can be logged
+impl Debug for Server {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Server {{ url: {:?}, authenticated: {} }}",
+            self.server.to_string(),
+            self.is_authenticated()
+        )
+    }
+}
+
+//
+
+// iiko reports some errors as plain text, e.g. "Error: Invalid key", which
+// would otherwise surface as a meaningless JSON parse error.
+pub(super) fn ensure_json(response: &str) -> Result<(), BotError> {
+    if response.trim_start().starts_with(['{', '[']) {
+        Ok(())
+    } else {
+        Err(BotError::IikoApi(response.trim().to_string()))
+    }
+}
+
+//
+
+#[derive(Clone)]
+pub(super) struct NewToken {
+    pub(super) id: String,
+    creation_time: DateTime<Utc>,
+    lifetime: Duration,
+}
+
+impl NewToken {
+    fn expires_at(&self) -> DateTime<Utc> {
+        self.creation_time + chrono::Duration::from_std(self.lifetime).unwrap()
+    }
+
+    fn is_expired(&self) -> bool {
+        Utc::now() >= self.expires_at()
+    }
+
+    fn will_expire_soon(&self, margin: Duration) -> bool {
+        Utc::now() + chrono::Duration::from_std(margin).unwrap() >= self.expires_at()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_output_leaves_out_credentials() {
+        let mut server = Server::new(
+            "admin",
+            "hunter2",
+            ServerConfig::from("example.iiko.it".to_string()),
+        );
+
+        server.token = Some(NewToken {
+            id: "secret-token".into(),
+            creation_time: Utc::now(),
+            lifetime: Duration::from_secs(3600),
+        });
+
+        let debug = format!("{:?}", server);
+
+        assert_eq!(
+            debug,
+            "Server { url: \"https://example.iiko.it\", authenticated: true }"
+        );
+        assert!(!debug.contains("hunter2"));
+        assert!(!debug.contains("secret-token"));
+    }
+}
@@ -0,0 +1,559 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    date::{
+        moscow_last_, moscow_last_month, moscow_now, moscow_quarter, moscow_start_of_month,
+        moscow_time,
+    },
+    error::BotError,
+    format::format_ruble,
+    shared::make_url,
+};
+
+use super::server::{Server, ensure_json};
+
+//
+
+pub enum Dates {
+    Week,
+    ThisMonth,
+    LastMonth,
+    Quarter(u8),
+    LastNDays(u32),
+    Custom { from: String, to: String },
+}
+
+//
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "UPPERCASE")]
+#[allow(clippy::upper_case_acronyms)]
+pub enum SessionStatus {
+    OPEN,
+    CLOSED,
+    ACCEPTED,
+    UNACCEPTED,
+    HASWARNINGS,
+}
+
+impl SessionStatus {
+    pub fn to_emoji(&self) -> &'static str {
+        match self {
+            Self::OPEN | Self::HASWARNINGS => "🟢",
+            Self::CLOSED => "🔴",
+            Self::ACCEPTED => "✅",
+            Self::UNACCEPTED => "⚠️",
+        }
+    }
+}
+
+impl Display for SessionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OPEN => write!(f, "Открыта"),
+            Self::CLOSED => write!(f, "Закрыта"),
+            Self::ACCEPTED => write!(f, "Принята"),
+            Self::UNACCEPTED => write!(f, "Не принята"),
+            Self::HASWARNINGS => write!(f, "Открыта (предупреждения)"),
+        }
+    }
+}
+
+//
+
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Shift {
+    pub id: String,
+    pub session_number: usize,
+    pub fiscal_number: usize,
+    pub cash_reg_number: usize,
+    pub cash_reg_serial: String,
+    pub open_date: String,
+    pub close_date: Option<String>,
+    pub accept_date: Option<String>,
+    pub manager_id: String,
+    pub responsible_user_id: Option<String>,
+    pub session_start_cash: usize,
+    pub pay_orders: f64,
+    pub sum_writeoff_orders: usize,
+    pub sales_cash: usize,
+    pub sales_credit: usize,
+    pub sales_card: f64,
+    pub pay_in: usize,
+    pub pay_out: usize,
+    pub pay_income: i32,
+    pub cash_remain: Option<usize>,
+    pub cash_diff: i32,
+    pub session_status: SessionStatus,
+    pub conception_id: Option<String>,
+}
+
+pub type Shifts = Vec<Shift>;
+
+#[allow(dead_code)]
+pub struct ShiftSummary {
+    pub total: f64,
+    pub cash: usize,
+    pub card: f64,
+    pub credit: usize,
+}
+
+pub struct ShiftComparison {
+    pub current: Shift,
+    pub previous: Shift,
+    pub delta_total: f64,
+    pub pct_change: f64,
+}
+
+pub fn compare_shifts(current: Shift, previous: Shift) -> ShiftComparison {
+    let delta_total = current.pay_orders - previous.pay_orders;
+
+    let pct_change = if previous.pay_orders == 0.0 {
+        0.0
+    } else {
+        delta_total / previous.pay_orders * 100.0
+    };
+
+    ShiftComparison {
+        current,
+        previous,
+        delta_total,
+        pct_change,
+    }
+}
+
+impl Shift {
+    pub fn open_datetime(&self) -> Option<NaiveDateTime> {
+        parse_iiko_datetime(&self.open_date)
+    }
+
+    pub fn close_datetime(&self) -> Option<NaiveDateTime> {
+        self.close_date.as_deref().and_then(parse_iiko_datetime)
+    }
+
+    /// Time the shift has been open so far, or its total length once closed
+    pub fn duration_secs(&self) -> Option<i64> {
+        let end = match self.session_status {
+            SessionStatus::OPEN | SessionStatus::HASWARNINGS => moscow_now(),
+            _ => self.close_datetime()?,
+        };
+
+        Some((end - self.open_datetime()?).num_seconds())
+    }
+}
+
+fn parse_iiko_datetime(date: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f")
+        .ok()
+        .or_else(|| NaiveDateTime::parse_from_str(date.get(..19)?, "%Y-%m-%dT%H:%M:%S").ok())
+}
+
+//
+
+fn date_range(date: Dates) -> Result<(String, String), BotError> {
+    Ok(match date {
+        Dates::Week => (moscow_last_(6), moscow_time().date_string()),
+        Dates::ThisMonth => (moscow_start_of_month(), moscow_time().date_string()),
+        Dates::LastMonth => moscow_last_month(),
+        Dates::Quarter(quarter) => moscow_quarter(quarter).ok_or(BotError::InvalidDateRange)?,
+        // The window ends today, so N days start N - 1 days ago
+        Dates::LastNDays(0) => return Err(BotError::InvalidDateRange),
+        Dates::LastNDays(days) => (moscow_last_(days as i64 - 1), moscow_time().date_string()),
+        Dates::Custom { from, to } => {
+            let from_date = NaiveDate::parse_from_str(&from, "%Y-%m-%d")
+                .map_err(|_| BotError::InvalidDateRange)?;
+            let to_date = NaiveDate::parse_from_str(&to, "%Y-%m-%d")
+                .map_err(|_| BotError::InvalidDateRange)?;
+
+            if from_date > to_date {
+                return Err(BotError::InvalidDateRange);
+            }
+
+            (from, to)
+        }
+    })
+}
+
+pub trait GetShifts {
+    async fn list_shifts_with_offset(server: &mut Server, date: Dates) -> Result<Shifts, BotError>;
+
+    fn nth_last_shift(shifts: Shifts, n: usize) -> Result<Shift, BotError>;
+
+    fn current_shift(shifts: Shifts) -> Result<Shift, BotError>;
+
+    fn latest_n_shifts(shifts: Shifts, n: usize) -> Vec<Shift>;
+
+    fn sum_pay_orders(shifts: &Shifts) -> f64;
+
+    fn group_shifts_by_date(shifts: &Shifts) -> Vec<(NaiveDate, f64, usize)>;
+
+    fn sum_shifts_by_week(shifts: &Shifts) -> Vec<((i32, u32), f64)>;
+
+    fn sum_shifts_by_type(shifts: &Shifts) -> ShiftSummary;
+
+    fn open_shifts(shifts: &Shifts) -> impl Iterator<Item = &Shift>;
+}
+
+impl GetShifts for Server {
+    async fn list_shifts_with_offset(server: &mut Self, date: Dates) -> Result<Shifts, BotError> {
+        server.auth().await?;
+
+        let url = make_url(
+            &server.server.url,
+            &["v2", "cashshifts", "list"],
+            server.server.use_https,
+        )?;
+
+        let (date_from, date_to) = date_range(date)?;
+
+        let response = server
+            .client
+            .get(url)
+            .query(&[
+                ("openDateFrom", date_from),
+                ("openDateTo", date_to),
+                ("status", "ANY".to_string()),
+                ("key", server.token.clone().unwrap().id),
+            ])
+            .send()
+            .await?
+            .text()
+            .await?;
+
+        ensure_json(&response)?;
+
+        let parsed: Shifts = serde_json::from_str(&response)?;
+
+        Ok(parsed)
+    }
+
+    fn nth_last_shift(shifts: Shifts, n: usize) -> Result<Shift, BotError> {
+        let len = shifts.len();
+
+        if n >= len {
+            return Err(BotError::NotFound(format!("Нет смены со сдвигом {}", n)));
+        }
+
+        let idx = len - n - 1;
+
+        shifts
+            .into_iter()
+            .nth(idx)
+            .ok_or_else(|| BotError::NotFound(format!("Нет смены со сдвигом {}", n)))
+    }
+
+    fn current_shift(shifts: Shifts) -> Result<Shift, BotError> {
+        Self::nth_last_shift(shifts, 0)
+    }
+
+    fn latest_n_shifts(shifts: Shifts, n: usize) -> Vec<Shift> {
+        shifts.into_iter().rev().take(n).collect()
+    }
+
+    fn sum_pay_orders(shifts: &Shifts) -> f64 {
+        shifts.iter().map(|shift| shift.pay_orders).sum()
+    }
+
+    fn group_shifts_by_date(shifts: &Shifts) -> Vec<(NaiveDate, f64, usize)> {
+        let mut days: BTreeMap<NaiveDate, (f64, usize)> = BTreeMap::new();
+
+        for shift in shifts {
+            let Some(date) = shift.open_datetime().map(|datetime| datetime.date()) else {
+                continue;
+            };
+
+            let day = days.entry(date).or_insert((0.0, 0));
+            day.0 += shift.pay_orders;
+            day.1 += 1;
+        }
+
+        days.into_iter()
+            .map(|(date, (sum, count))| (date, sum, count))
+            .collect()
+    }
+
+    // Keyed by ISO year too, so week 52 of December sorts before week 1 of January
+    fn sum_shifts_by_week(shifts: &Shifts) -> Vec<((i32, u32), f64)> {
+        let mut weeks: BTreeMap<(i32, u32), f64> = BTreeMap::new();
+
+        for shift in shifts {
+            let Some(datetime) = shift.open_datetime() else {
+                continue;
+            };
+
+            let week = datetime.iso_week();
+
+            *weeks.entry((week.year(), week.week())).or_insert(0.0) += shift.pay_orders;
+        }
+
+        weeks.into_iter().collect()
+    }
+
+    fn sum_shifts_by_type(shifts: &Shifts) -> ShiftSummary {
+        ShiftSummary {
+            total: Self::sum_pay_orders(shifts),
+            cash: shifts.iter().map(|shift| shift.sales_cash).sum(),
+            card: shifts.iter().map(|shift| shift.sales_card).sum(),
+            credit: shifts.iter().map(|shift| shift.sales_credit).sum(),
+        }
+    }
+
+    fn open_shifts(shifts: &Shifts) -> impl Iterator<Item = &Shift> {
+        shifts
+            .iter()
+            .filter(|shift| shift.session_status == SessionStatus::OPEN)
+    }
+}
+
+//
+
+pub fn display_shifts_table(shifts: &[Shift]) -> String {
+    if shifts.is_empty() {
+        return "Смен не найдено".to_string();
+    }
+
+    let mut table = String::new();
+
+    table.push_str("```\n");
+    table.push_str(&format!(
+        "{:<10} | {:>5} | {:>12} | {}\n",
+        "Дата", "№", "Итог", "Статус"
+    ));
+
+    for shift in shifts {
+        let date = shift.open_date.get(..10).unwrap_or(&shift.open_date);
+
+        table.push_str(&format!(
+            "{:<10} | {:>5} | {:>12} | {}\n",
+            date,
+            shift.session_number,
+            format_ruble(shift.pay_orders),
+            shift.session_status.to_emoji()
+        ));
+    }
+
+    table.push_str("```");
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shift(session_number: usize, open_date: &str, pay_orders: f64) -> Shift {
+        Shift {
+            id: session_number.to_string(),
+            session_number,
+            fiscal_number: session_number,
+            cash_reg_number: 1,
+            cash_reg_serial: "serial".into(),
+            open_date: open_date.into(),
+            close_date: None,
+            accept_date: None,
+            manager_id: "manager".into(),
+            responsible_user_id: None,
+            session_start_cash: 0,
+            pay_orders,
+            sum_writeoff_orders: 0,
+            sales_cash: 0,
+            sales_credit: 0,
+            sales_card: 0.0,
+            pay_in: 0,
+            pay_out: 0,
+            pay_income: 0,
+            cash_remain: None,
+            cash_diff: 0,
+            session_status: SessionStatus::CLOSED,
+            conception_id: None,
+        }
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn group_shifts_by_date_uses_the_opening_day() {
+        let shifts = vec![
+            shift(1, "2024-01-14T23:50:00.000", 100.0),
+            shift(2, "2024-01-15T00:10:00.000", 50.0),
+            shift(3, "2024-01-15T09:00:00", 25.5),
+        ];
+
+        assert_eq!(
+            Server::group_shifts_by_date(&shifts),
+            vec![(date(2024, 1, 14), 100.0, 1), (date(2024, 1, 15), 75.5, 2)]
+        );
+    }
+
+    #[test]
+    fn group_shifts_by_date_sorts_days_and_skips_bad_dates() {
+        let shifts = vec![
+            shift(1, "2024-02-01T10:00:00", 10.0),
+            shift(2, "not a date", 99.0),
+            shift(3, "2024-01-31T10:00:00", 20.0),
+        ];
+
+        assert_eq!(
+            Server::group_shifts_by_date(&shifts),
+            vec![(date(2024, 1, 31), 20.0, 1), (date(2024, 2, 1), 10.0, 1)]
+        );
+    }
+
+    #[test]
+    fn compare_shifts_computes_delta_and_percentage() {
+        let comparison = compare_shifts(shift(2, "", 1500.0), shift(1, "", 1000.0));
+
+        assert_eq!(comparison.delta_total, 500.0);
+        assert_eq!(comparison.pct_change, 50.0);
+
+        let comparison = compare_shifts(shift(2, "", 750.0), shift(1, "", 1000.0));
+
+        assert_eq!(comparison.delta_total, -250.0);
+        assert_eq!(comparison.pct_change, -25.0);
+    }
+
+    #[test]
+    fn compare_shifts_with_empty_previous_shift_has_no_percentage() {
+        let comparison = compare_shifts(shift(2, "", 1000.0), shift(1, "", 0.0));
+
+        assert_eq!(comparison.delta_total, 1000.0);
+        assert_eq!(comparison.pct_change, 0.0);
+    }
+
+    #[test]
+    fn sum_shifts_by_type_of_no_shifts_is_zero() {
+        let summary = Server::sum_shifts_by_type(&Vec::new());
+
+        assert_eq!(summary.total, 0.0);
+        assert_eq!(summary.cash, 0);
+        assert_eq!(summary.card, 0.0);
+        assert_eq!(summary.credit, 0);
+    }
+
+    #[test]
+    fn sum_shifts_by_type_adds_up_each_payment_type() {
+        let shifts = vec![
+            Shift {
+                sales_cash: 300,
+                sales_card: 700.5,
+                ..shift(1, "", 1000.5)
+            },
+            Shift {
+                sales_cash: 100,
+                sales_credit: 50,
+                ..shift(2, "", 150.0)
+            },
+        ];
+
+        let summary = Server::sum_shifts_by_type(&shifts);
+
+        assert_eq!(summary.total, 1150.5);
+        assert_eq!(summary.cash, 400);
+        assert_eq!(summary.card, 700.5);
+        assert_eq!(summary.credit, 50);
+    }
+
+    #[test]
+    fn session_status_display() {
+        assert_eq!(SessionStatus::OPEN.to_string(), "Открыта");
+        assert_eq!(SessionStatus::CLOSED.to_string(), "Закрыта");
+        assert_eq!(SessionStatus::ACCEPTED.to_string(), "Принята");
+        assert_eq!(SessionStatus::UNACCEPTED.to_string(), "Не принята");
+        assert_eq!(
+            SessionStatus::HASWARNINGS.to_string(),
+            "Открыта (предупреждения)"
+        );
+    }
+
+    #[test]
+    fn session_status_emoji() {
+        assert_eq!(SessionStatus::OPEN.to_emoji(), "🟢");
+        assert_eq!(SessionStatus::HASWARNINGS.to_emoji(), "🟢");
+        assert_eq!(SessionStatus::CLOSED.to_emoji(), "🔴");
+        assert_eq!(SessionStatus::ACCEPTED.to_emoji(), "✅");
+        assert_eq!(SessionStatus::UNACCEPTED.to_emoji(), "⚠️");
+    }
+
+    fn numbered_shifts(count: usize) -> Shifts {
+        (1..=count).map(|n| shift(n, "", 0.0)).collect()
+    }
+
+    #[test]
+    fn nth_last_shift_counts_from_the_newest() {
+        let newest = Server::nth_last_shift(numbered_shifts(3), 0).unwrap();
+        assert_eq!(newest.session_number, 3);
+
+        let oldest = Server::nth_last_shift(numbered_shifts(3), 2).unwrap();
+        assert_eq!(oldest.session_number, 1);
+    }
+
+    #[test]
+    fn nth_last_shift_out_of_range_is_not_found() {
+        assert!(matches!(
+            Server::nth_last_shift(numbered_shifts(3), 3),
+            Err(BotError::NotFound(_))
+        ));
+        assert!(matches!(
+            Server::nth_last_shift(Vec::new(), 0),
+            Err(BotError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn current_shift_is_the_newest() {
+        let current = Server::current_shift(numbered_shifts(2)).unwrap();
+        assert_eq!(current.session_number, 2);
+    }
+
+    #[test]
+    fn sum_pay_orders_of_no_shifts_is_zero() {
+        assert_eq!(Server::sum_pay_orders(&Vec::new()), 0.0);
+    }
+
+    #[test]
+    fn sum_pay_orders_keeps_kopecks() {
+        let shifts = vec![shift(1, "", 100.50), shift(2, "", 200.75)];
+
+        assert_eq!(Server::sum_pay_orders(&shifts), 301.25);
+    }
+
+    #[test]
+    fn date_range_rejects_invalid_quarters() {
+        for quarter in [0, 5] {
+            assert!(matches!(
+                date_range(Dates::Quarter(quarter)),
+                Err(BotError::InvalidDateRange)
+            ));
+        }
+    }
+
+    #[test]
+    fn sum_shifts_by_week_orders_weeks_across_new_year() {
+        let shifts = vec![
+            shift(1, "2024-12-23T10:00:00", 10.0),
+            shift(2, "2024-12-30T10:00:00", 20.0),
+            shift(3, "2025-01-01T10:00:00", 30.0),
+            shift(4, "2025-01-06T10:00:00", 40.0),
+            shift(5, "2023-12-25T10:00:00", 5.0),
+        ];
+
+        // 30.12.2024 and 01.01.2025 both fall into ISO week 1 of 2025
+        assert_eq!(
+            Server::sum_shifts_by_week(&shifts),
+            vec![
+                ((2023, 52), 5.0),
+                ((2024, 52), 10.0),
+                ((2025, 1), 50.0),
+                ((2025, 2), 40.0)
+            ]
+        );
+    }
+}
@@ -1,14 +1,27 @@
+mod cache;
+mod circuit_breaker;
 mod date;
+mod error;
+mod export;
+mod format;
 mod iiko;
 mod olap;
+mod rate_limit;
+mod server_pool;
 mod shared;
 mod tg;
+mod users;
 
 use crate::tg::initialise;
 use std::error::Error;
+use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
     initialise().await?;
     Ok(())
 }
@@ -2,8 +2,10 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
+use crate::date::moscow_time;
+
 #[derive(Deserialize, Debug)]
-#[allow(non_snake_case)]
+#[allow(non_snake_case, clippy::upper_case_acronyms)]
 pub struct OLAP {
     pub DishCategory: Option<String>,
     pub DishDiscountSumInt: f64,
@@ -16,6 +18,13 @@ pub struct OLAPList {
     pub data: Vec<OLAP>,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IikoApiError {
+    pub error: String,
+    pub error_description: String,
+}
+
 #[derive(Clone, Debug)]
 #[allow(non_snake_case)]
 pub struct OlapElement {
@@ -26,6 +35,50 @@ pub struct OlapElement {
 
 pub type OlapMap = HashMap<String, Vec<OlapElement>>;
 
+pub const OLAP_NO_DATA: &str = "Нет данных для отображения";
+
+#[derive(Clone, Debug)]
+pub struct CategoryDelta {
+    pub current_revenue: f64,
+    pub previous_revenue: f64,
+    pub delta: f64,
+    pub pct: f64,
+}
+
+pub fn compare_olap_maps(current: &OlapMap, previous: &OlapMap) -> HashMap<String, CategoryDelta> {
+    let revenue = |map: &OlapMap, category: &str| -> f64 {
+        map.get(category)
+            .map(|elements| elements.iter().map(|e| e.DishDiscountSumInt).sum())
+            .unwrap_or(0.0)
+    };
+
+    current
+        .keys()
+        .chain(previous.keys())
+        .map(|category| {
+            let current_revenue = revenue(current, category);
+            let previous_revenue = revenue(previous, category);
+            let delta = current_revenue - previous_revenue;
+
+            let pct = if previous_revenue == 0.0 {
+                0.0
+            } else {
+                delta / previous_revenue * 100.0
+            };
+
+            (
+                category.clone(),
+                CategoryDelta {
+                    current_revenue,
+                    previous_revenue,
+                    delta,
+                    pct,
+                },
+            )
+        })
+        .collect()
+}
+
 pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
     let mut lines = Vec::new();
     let mut current = String::new();
@@ -34,6 +87,15 @@ pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
             lines.push(current.clone());
             current.clear();
         }
+        if word.chars().count() > width {
+            let chars: Vec<char> = word.chars().collect();
+            let mut chunks = chars.chunks(width.max(1));
+            if let Some(last) = chunks.next_back() {
+                lines.extend(chunks.map(|chunk| chunk.iter().collect::<String>()));
+                current = last.iter().collect();
+            }
+            continue;
+        }
         if !current.is_empty() {
             current.push(' ');
         }
@@ -45,33 +107,86 @@ pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
     lines
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+pub enum SortOrder {
+    ByRevenue,
+    #[default]
+    ByGuestNum,
+    ByName,
+}
+
+impl SortOrder {
+    pub fn next(self) -> Self {
+        match self {
+            Self::ByRevenue => Self::ByGuestNum,
+            Self::ByGuestNum => Self::ByName,
+            Self::ByName => Self::ByRevenue,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::ByRevenue => "по выручке ↓",
+            Self::ByGuestNum => "по заказам ↓",
+            Self::ByName => "по названию ↑",
+        }
+    }
+}
+
+pub fn olap_page_count(len: usize, page_size: usize) -> usize {
+    len.div_ceil(page_size.max(1)).max(1)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum ReportType {
     SALES,
+    DELIVERIES,
+    WRITEOFFS,
+    TRANSACTIONS,
+}
+
+impl ReportType {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::SALES => "Продажи",
+            Self::DELIVERIES => "Доставки",
+            Self::WRITEOFFS => "Списания",
+            Self::TRANSACTIONS => "Проводки",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-#[allow(non_camel_case_types)]
+#[allow(non_camel_case_types, dead_code)]
 pub enum FilterType {
     dateRange,
     includeValues,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[allow(non_camel_case_types)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 pub enum PeriodType {
     CURRENT_MONTH,
+    PREVIOUS_MONTH,
+    CURRENT_WEEK,
+    PREVIOUS_WEEK,
+    YESTERDAY,
+    TODAY,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "filterType")]
 #[allow(non_snake_case)]
 pub enum Filter {
     DateRange { periodType: PeriodType, to: String },
     IncludeValues { values: Vec<String> },
+    ExcludeValues { values: Vec<String> },
+    NumberRange { from: f64, to: f64 },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ReportConfig {
     #[serde(rename = "reportType")]
     pub report_type: ReportType,
@@ -87,3 +202,290 @@ pub struct ReportConfig {
 
     pub filters: HashMap<String, Filter>,
 }
+
+// Either drops deleted orders explicitly or keeps only the ones never deleted
+fn order_deleted_filter(exclude_deleted: bool) -> Filter {
+    if exclude_deleted {
+        Filter::ExcludeValues {
+            values: vec!["DELETED".into()],
+        }
+    } else {
+        Filter::IncludeValues {
+            values: vec!["NOT_DELETED".into()],
+        }
+    }
+}
+
+impl ReportConfig {
+    pub fn sales_by_category(exclude_deleted: bool) -> Self {
+        Self::current_month(
+            ReportType::SALES,
+            "DishCategory",
+            "DishName",
+            exclude_deleted,
+        )
+    }
+
+    pub fn sales_by_dish(exclude_deleted: bool) -> Self {
+        Self::current_month(
+            ReportType::SALES,
+            "DishName",
+            "DishCategory",
+            exclude_deleted,
+        )
+    }
+
+    pub fn writeoffs_by_category(exclude_deleted: bool) -> Self {
+        Self::current_month(
+            ReportType::WRITEOFFS,
+            "DishCategory",
+            "DishName",
+            exclude_deleted,
+        )
+    }
+
+    fn current_month(
+        report_type: ReportType,
+        row_field: &str,
+        col_field: &str,
+        exclude_deleted: bool,
+    ) -> Self {
+        let mut filters = HashMap::new();
+
+        filters.insert(
+            "OpenDate.Typed".into(),
+            Filter::DateRange {
+                periodType: PeriodType::CURRENT_MONTH,
+                to: moscow_time().date_string(),
+            },
+        );
+
+        filters.insert("OrderDeleted".into(), order_deleted_filter(exclude_deleted));
+
+        Self {
+            report_type,
+            group_by_row_fields: vec![row_field.into()],
+            group_by_col_fields: vec![col_field.into()],
+            aggregate_fields: vec!["GuestNum".into(), "DishDiscountSumInt".into()],
+            filters,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ReportConfigBuilder {
+    report_type: Option<ReportType>,
+    group_by_row_fields: Vec<String>,
+    group_by_col_fields: Vec<String>,
+    aggregate_fields: Vec<String>,
+    filters: HashMap<String, Filter>,
+}
+
+impl ReportConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn report_type(mut self, report_type: ReportType) -> Self {
+        self.report_type = Some(report_type);
+        self
+    }
+
+    pub fn row_field(mut self, field: impl Into<String>) -> Self {
+        self.group_by_row_fields.push(field.into());
+        self
+    }
+
+    pub fn col_field(mut self, field: impl Into<String>) -> Self {
+        self.group_by_col_fields.push(field.into());
+        self
+    }
+
+    pub fn aggregate(mut self, field: impl Into<String>) -> Self {
+        self.aggregate_fields.push(field.into());
+        self
+    }
+
+    pub fn filter(mut self, field: impl Into<String>, filter: Filter) -> Self {
+        self.filters.insert(field.into(), filter);
+        self
+    }
+
+    pub fn exclude_deleted(self, exclude_deleted: bool) -> Self {
+        self.filter("OrderDeleted", order_deleted_filter(exclude_deleted))
+    }
+
+    pub fn build(self) -> Result<ReportConfig, String> {
+        let report_type = self.report_type.ok_or("Не указан тип отчёта")?;
+
+        if self.aggregate_fields.is_empty() {
+            return Err("Не указано ни одного поля агрегации".into());
+        }
+
+        Ok(ReportConfig {
+            report_type,
+            group_by_row_fields: self.group_by_row_fields,
+            group_by_col_fields: self.group_by_col_fields,
+            aggregate_fields: self.aggregate_fields,
+            filters: self.filters,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{from_str, json, to_string, to_value};
+
+    use super::*;
+
+    #[test]
+    fn period_type_serializes_as_its_name() {
+        for (period, name) in [
+            (PeriodType::CURRENT_MONTH, "CURRENT_MONTH"),
+            (PeriodType::PREVIOUS_MONTH, "PREVIOUS_MONTH"),
+            (PeriodType::CURRENT_WEEK, "CURRENT_WEEK"),
+            (PeriodType::PREVIOUS_WEEK, "PREVIOUS_WEEK"),
+            (PeriodType::YESTERDAY, "YESTERDAY"),
+            (PeriodType::TODAY, "TODAY"),
+        ] {
+            let serialized = to_string(&period).unwrap();
+
+            assert_eq!(serialized, format!("\"{}\"", name));
+            assert_eq!(from_str::<PeriodType>(&serialized).unwrap(), period);
+        }
+    }
+
+    #[test]
+    fn exclude_values_filter_json() {
+        let filter = Filter::ExcludeValues {
+            values: vec!["Напитки".into(), "Алкоголь".into()],
+        };
+
+        assert_eq!(
+            to_value(&filter).unwrap(),
+            json!({ "filterType": "ExcludeValues", "values": ["Напитки", "Алкоголь"] })
+        );
+    }
+
+    #[test]
+    fn number_range_filter_json() {
+        let filter = Filter::NumberRange {
+            from: 10000.0,
+            to: 999999999.0,
+        };
+
+        assert_eq!(
+            to_value(&filter).unwrap(),
+            json!({ "filterType": "NumberRange", "from": 10000.0, "to": 999999999.0 })
+        );
+    }
+
+    #[test]
+    fn number_range_filter_round_trip() {
+        let json = r#"{"filterType":"NumberRange","from":100.5,"to":200}"#;
+
+        match from_str::<Filter>(json).unwrap() {
+            Filter::NumberRange { from, to } => {
+                assert_eq!(from, 100.5);
+                assert_eq!(to, 200.0);
+            }
+            other => panic!("unexpected filter {:?}", other),
+        }
+    }
+
+    #[test]
+    fn report_type_serializes_as_its_name() {
+        for (report_type, name) in [
+            (ReportType::SALES, "SALES"),
+            (ReportType::DELIVERIES, "DELIVERIES"),
+            (ReportType::WRITEOFFS, "WRITEOFFS"),
+            (ReportType::TRANSACTIONS, "TRANSACTIONS"),
+        ] {
+            let serialized = to_string(&report_type).unwrap();
+
+            assert_eq!(serialized, format!("\"{}\"", name));
+            assert_eq!(from_str::<ReportType>(&serialized).unwrap(), report_type);
+        }
+    }
+
+    #[test]
+    fn wrap_text_keeps_a_word_of_exactly_width() {
+        assert_eq!(wrap_text("Пицца", 5), vec!["Пицца"]);
+    }
+
+    #[test]
+    fn wrap_text_splits_words_longer_than_width() {
+        assert_eq!(wrap_text("Пиццаа", 5), vec!["Пицца", "а"]);
+        assert_eq!(wrap_text("ПиццаПицца", 5), vec!["Пицца", "Пицца"]);
+    }
+
+    #[test]
+    fn wrap_text_mixes_short_and_long_words() {
+        assert_eq!(wrap_text("ab abcdefg cd", 5), vec!["ab", "abcde", "fg cd"]);
+    }
+
+    #[test]
+    fn builder_exclude_deleted_picks_the_order_deleted_filter() {
+        let config = |exclude| {
+            ReportConfigBuilder::new()
+                .report_type(ReportType::SALES)
+                .aggregate("DishDiscountSumInt")
+                .exclude_deleted(exclude)
+                .build()
+                .unwrap()
+        };
+
+        assert_eq!(
+            to_value(&config(true).filters["OrderDeleted"]).unwrap(),
+            json!({ "filterType": "ExcludeValues", "values": ["DELETED"] })
+        );
+        assert_eq!(
+            to_value(&config(false).filters["OrderDeleted"]).unwrap(),
+            json!({ "filterType": "IncludeValues", "values": ["NOT_DELETED"] })
+        );
+    }
+
+    fn preset_json(report_type: &str, row_field: &str, col_field: &str) -> serde_json::Value {
+        json!({
+            "reportType": report_type,
+            "groupByRowFields": [row_field],
+            "groupByColFields": [col_field],
+            "aggregateFields": ["GuestNum", "DishDiscountSumInt"],
+            "filters": {
+                "OpenDate.Typed": {
+                    "filterType": "DateRange",
+                    "periodType": "CURRENT_MONTH",
+                    "to": moscow_time().date_string(),
+                },
+                "OrderDeleted": {
+                    "filterType": "IncludeValues",
+                    "values": ["NOT_DELETED"],
+                },
+            },
+        })
+    }
+
+    #[test]
+    fn sales_by_category_preset_json() {
+        assert_eq!(
+            to_value(ReportConfig::sales_by_category(false)).unwrap(),
+            preset_json("SALES", "DishCategory", "DishName")
+        );
+    }
+
+    #[test]
+    fn sales_by_dish_preset_json() {
+        assert_eq!(
+            to_value(ReportConfig::sales_by_dish(false)).unwrap(),
+            preset_json("SALES", "DishName", "DishCategory")
+        );
+    }
+
+    #[test]
+    fn writeoffs_by_category_preset_json() {
+        assert_eq!(
+            to_value(ReportConfig::writeoffs_by_category(false)).unwrap(),
+            preset_json("WRITEOFFS", "DishCategory", "DishName")
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use teloxide::types::ChatId;
+use tokio::sync::Mutex;
+
+//
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Default)]
+pub struct RateLimiter(Arc<Mutex<HashMap<ChatId, VecDeque<Instant>>>>);
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns false when the chat has already made `max_per_minute` requests
+    // within the last minute. Rejected requests aren't counted.
+    pub async fn check(&self, id: ChatId, max_per_minute: u32) -> bool {
+        let mut chats = self.0.lock().await;
+        let requests = chats.entry(id).or_default();
+        let now = Instant::now();
+
+        while let Some(oldest) = requests.front() {
+            if now.duration_since(*oldest) < WINDOW {
+                break;
+            }
+            requests.pop_front();
+        }
+
+        if requests.len() >= max_per_minute as usize {
+            return false;
+        }
+
+        requests.push_back(now);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn check_rejects_requests_over_the_limit() {
+        let limiter = RateLimiter::new();
+
+        for _ in 0..3 {
+            assert!(limiter.check(ChatId(1), 3).await);
+        }
+
+        assert!(!limiter.check(ChatId(1), 3).await);
+    }
+
+    #[tokio::test]
+    async fn check_counts_each_chat_separately() {
+        let limiter = RateLimiter::new();
+
+        assert!(limiter.check(ChatId(1), 1).await);
+        assert!(!limiter.check(ChatId(1), 1).await);
+        assert!(limiter.check(ChatId(2), 1).await);
+    }
+
+    #[tokio::test]
+    async fn check_forgets_requests_older_than_a_minute() {
+        let limiter = RateLimiter::new();
+
+        let expired = Instant::now().checked_sub(WINDOW).unwrap();
+        limiter
+            .0
+            .lock()
+            .await
+            .insert(ChatId(1), VecDeque::from([expired]));
+
+        assert!(limiter.check(ChatId(1), 1).await);
+    }
+}
@@ -0,0 +1,105 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use crate::{circuit_breaker::CircuitState, iiko::Server, shared::ServerConfig};
+
+//
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+const REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+pub type PooledServer = Arc<Mutex<Server>>;
+
+/// Authenticated servers shared between requests, keyed by URL
+#[derive(Clone, Default)]
+pub struct ServerPool(Arc<Mutex<HashMap<String, PooledServer>>>);
+
+impl ServerPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn get<F>(&self, server: &ServerConfig, connect: F) -> PooledServer
+    where
+        F: FnOnce() -> Server,
+    {
+        self.0
+            .lock()
+            .await
+            .entry(server.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(connect())))
+            .clone()
+    }
+
+    /// Circuit state of an already pooled server, unless it is busy right now
+    pub async fn circuit_state(&self, server: &ServerConfig) -> Option<CircuitState> {
+        let pooled = self.0.lock().await.get(&server.to_string())?.clone();
+
+        let state = pooled.try_lock().ok()?.circuit_state();
+
+        Some(state)
+    }
+
+    async fn snapshot(&self) -> Vec<(String, PooledServer)> {
+        self.0
+            .lock()
+            .await
+            .iter()
+            .map(|(url, server)| (url.clone(), server.clone()))
+            .collect()
+    }
+
+    /// Re-authenticates idle servers whose tokens are about to expire
+    pub async fn refresh(&self) {
+        for (url, server) in self.snapshot().await {
+            // A busy server is serving a request which re-authenticates on its own
+            let Ok(mut server) = server.try_lock() else {
+                continue;
+            };
+
+            if !server.expires_within(REFRESH_MARGIN) {
+                continue;
+            }
+
+            if let Err(e) = server.force_reauth().await {
+                tracing::warn!(url = %url, error = %e, "failed to refresh pooled token");
+            }
+        }
+    }
+
+    pub fn spawn_refresher(&self, cancel: CancellationToken) {
+        let pool = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = interval.tick() => pool.refresh().await,
+                }
+            }
+        });
+    }
+
+    /// Drops a server that was removed from the config and logs out of it
+    pub async fn remove(&self, server: &ServerConfig) {
+        let Some(pooled) = self.0.lock().await.remove(&server.to_string()) else {
+            return;
+        };
+
+        if let Err(e) = pooled.lock().await.deauth().await {
+            tracing::warn!(url = %server, error = %e, "failed to log out of server");
+        }
+    }
+
+    pub async fn deauth_all(&self) {
+        for (url, server) in self.snapshot().await {
+            if let Err(e) = server.lock().await.deauth().await {
+                tracing::warn!(url = %url, error = %e, "failed to log out of server");
+            }
+        }
+    }
+}
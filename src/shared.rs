@@ -1,36 +1,159 @@
-use serde::de::DeserializeOwned;
+use std::{fmt::Display, path::Path};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use sha1::{Digest, Sha1};
-use std::error::Error;
+use sha2::Sha256;
 use tokio::fs;
 use toml::from_str;
 
-pub async fn read_to_struct<T: DeserializeOwned, S: AsRef<str>>(
+use crate::error::BotError;
+
+pub async fn read_to_struct<T: DeserializeOwned, S: AsRef<str>>(path: S) -> Result<T, BotError> {
+    let path = path.as_ref();
+
+    let context = |e: &dyn Display| BotError::Config(format!("Error reading '{}': {}", path, e));
+
+    let file = fs::read_to_string(path).await.map_err(|e| context(&e))?;
+
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&file).map_err(|e| context(&e)),
+        Some("toml") => from_str(&file).map_err(|e| context(&e)),
+        _ => Err(BotError::Config(format!(
+            "Unsupported config format: '{}'",
+            path
+        ))),
+    }
+}
+
+pub async fn write_struct<T: Serialize, S: AsRef<str>>(path: S, value: &T) -> Result<(), BotError> {
+    let path = path.as_ref();
+
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("json") => atomic_write_json(path, value).await,
+        Some("toml") => atomic_write_toml(path, value).await,
+        _ => Err(BotError::Config(format!(
+            "Unsupported config format: '{}'",
+            path
+        ))),
+    }
+}
+
+pub async fn atomic_write_toml<T: Serialize, S: AsRef<str>>(
     path: S,
-) -> Result<T, Box<dyn Error>> {
-    let file = fs::read_to_string(path.as_ref()).await?;
+    value: &T,
+) -> Result<(), BotError> {
+    atomic_write(path.as_ref(), toml::to_string(value)?).await
+}
+
+pub async fn atomic_write_json<T: Serialize, S: AsRef<str>>(
+    path: S,
+    value: &T,
+) -> Result<(), BotError> {
+    let path = path.as_ref();
+
+    let contents = serde_json::to_string_pretty(value)
+        .map_err(|e| BotError::Config(format!("Error writing '{}': {}", path, e)))?;
 
-    Ok(from_str(&file)?)
+    atomic_write(path, contents).await
 }
 
+// Writing next to the target and renaming over it means a crash mid-write
+// never leaves a truncated file behind.
+async fn atomic_write(path: &str, contents: String) -> Result<(), BotError> {
+    let tmp_path = format!("{}.tmp", path);
+
+    fs::write(&tmp_path, contents).await?;
+    fs::rename(&tmp_path, path).await?;
+
+    Ok(())
+}
+
+#[deprecated(note = "SHA-1 is kept only for older configs, use sha256sum")]
 pub fn sha1sum<S: AsRef<str>>(pass: S) -> String {
     format!("{:x}", Sha1::digest(pass.as_ref().as_bytes()))
 }
 
-use std::usize;
+pub fn sha256sum<S: AsRef<str>>(pass: S) -> String {
+    format!("{:x}", Sha256::digest(pass.as_ref().as_bytes()))
+}
+
+//
+
+/// An iiko server address; a plain string in the config means HTTPS
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(from = "ServerConfigRepr")]
+pub struct ServerConfig {
+    pub url: String,
+    pub use_https: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ServerConfigRepr {
+    Url(String),
+    Full {
+        url: String,
+        #[serde(default = "default_use_https")]
+        use_https: bool,
+    },
+}
+
+fn default_use_https() -> bool {
+    true
+}
+
+impl From<ServerConfigRepr> for ServerConfig {
+    fn from(repr: ServerConfigRepr) -> Self {
+        match repr {
+            ServerConfigRepr::Url(url) => url.into(),
+            ServerConfigRepr::Full { url, use_https } => Self { url, use_https },
+        }
+    }
+}
+
+impl From<String> for ServerConfig {
+    fn from(url: String) -> Self {
+        Self {
+            url,
+            use_https: true,
+        }
+    }
+}
+
+impl Display for ServerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", scheme(self.use_https), self.url)
+    }
+}
+
+//
+
+const HTTPS: &str = "https://";
+const HTTP: &str = "http://";
+const MIDDLE: &str = "/resto/api";
+
+fn scheme(use_https: bool) -> &'static str {
+    if use_https { HTTPS } else { HTTP }
+}
 
-const HTTPS: &'static str = "https://";
-const MIDDLE: &'static str = "/resto/api";
+pub fn make_url(server: &str, path: &[&str], use_https: bool) -> Result<String, BotError> {
+    let server = server.trim_end_matches('/');
+
+    if server.is_empty() {
+        return Err(BotError::Config("Пустой адрес сервера".into()));
+    }
+
+    let scheme = scheme(use_https);
 
-pub fn make_url(server: &String, path: &[&str]) -> String {
     let mut string = String::with_capacity(
-        HTTPS.len()
+        scheme.len()
             + server.len()
             + MIDDLE.len()
             + path.len()
             + path.iter().map(|element| element.len()).sum::<usize>(),
     );
 
-    string.push_str(HTTPS);
+    string.push_str(scheme);
 
     string.push_str(server);
 
@@ -38,10 +161,126 @@ pub fn make_url(server: &String, path: &[&str]) -> String {
 
     for element in path {
         string.push('/');
-        string.push_str(&element);
+        string.push_str(element);
+    }
+
+    tracing::debug!(url = %string, "built iiko API URL");
+
+    Ok(string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("iiko-bot-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn make_url_joins_path_segments() {
+        assert_eq!(
+            make_url("example.iiko.it", &["auth"], true).unwrap(),
+            "https://example.iiko.it/resto/api/auth"
+        );
+        assert_eq!(
+            make_url("example.iiko.it", &["v2", "reports", "olap"], false).unwrap(),
+            "http://example.iiko.it/resto/api/v2/reports/olap"
+        );
+    }
+
+    #[test]
+    fn make_url_rejects_an_empty_server() {
+        assert!(make_url("", &["auth"], true).is_err());
+        assert!(make_url("/", &["auth"], true).is_err());
+    }
+
+    #[test]
+    fn make_url_strips_trailing_slashes() {
+        assert_eq!(
+            make_url("example.iiko.it/", &["auth"], true).unwrap(),
+            "https://example.iiko.it/resto/api/auth"
+        );
+    }
+
+    #[test]
+    fn make_url_keeps_the_port() {
+        assert_eq!(
+            make_url("10.0.0.5:8080", &["auth"], false).unwrap(),
+            "http://10.0.0.5:8080/resto/api/auth"
+        );
+    }
+
+    #[tokio::test]
+    async fn atomic_write_replaces_the_file_and_cleans_up() {
+        let path = temp_path("atomic.toml");
+
+        fs::write(&path, "old").await.unwrap();
+        atomic_write(&path, "new".into()).await.unwrap();
+
+        assert_eq!(fs::read_to_string(&path).await.unwrap(), "new");
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
+
+        fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn interrupted_atomic_write_keeps_the_old_contents() {
+        let path = temp_path("interrupted.toml");
+        let tmp_path = format!("{}.tmp", path);
+
+        fs::write(&path, "old").await.unwrap();
+
+        // A directory in the way makes the temporary write fail midway
+        fs::create_dir(&tmp_path).await.unwrap();
+
+        assert!(atomic_write(&path, "new".into()).await.is_err());
+        assert_eq!(fs::read_to_string(&path).await.unwrap(), "old");
+
+        fs::remove_dir(&tmp_path).await.unwrap();
+        fs::remove_file(&path).await.unwrap();
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Sample {
+        name: String,
+        chats: Vec<i64>,
+        enabled: bool,
     }
 
-    println!("{string}");
+    async fn assert_round_trip(path: &str) {
+        let sample = Sample {
+            name: "Главный".into(),
+            chats: vec![1, -100],
+            enabled: true,
+        };
 
-    string
+        write_struct(path, &sample).await.unwrap();
+        let read: Sample = read_to_struct(path).await.unwrap();
+
+        assert_eq!(read, sample);
+
+        fs::remove_file(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_struct_round_trips_toml() {
+        assert_round_trip(&temp_path("round-trip.toml")).await;
+    }
+
+    #[tokio::test]
+    async fn write_struct_round_trips_json() {
+        assert_round_trip(&temp_path("round-trip.json")).await;
+    }
+
+    #[tokio::test]
+    async fn write_struct_rejects_unknown_extensions() {
+        let path = temp_path("round-trip.yaml");
+
+        assert!(write_struct(&path, &1).await.is_err());
+        assert!(!Path::new(&path).exists());
+    }
 }
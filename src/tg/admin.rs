@@ -0,0 +1,440 @@
+use crate::error::BotError;
+use crate::format::split_message;
+use crate::shared::read_to_struct;
+use crate::users::PersistentUserList;
+
+//
+
+use std::collections::HashSet;
+
+//
+
+use teloxide::Bot;
+use teloxide::payloads::SendMessageSetters;
+use teloxide::prelude::Requester;
+use teloxide::types::{Message, ParseMode};
+
+//
+
+use super::keyboard::{
+    build_admin_keyboard, build_delete_user_confirm_keyboard, build_delete_user_keyboard,
+    delete_user_confirm_label,
+};
+use super::{
+    ADMIN_LIST_USERS, CANCEL, DELETE_USER_CANCEL, DependenciesForDispatcher, MyDialogue,
+    SharedAdmins, State, TELEGRAM_MAX_MESSAGE_LEN, TG_CFG_PATH, TgCfg, env_override, handle_start,
+    is_admin, message_text, normalize_username, sender_username,
+};
+
+//
+
+pub(super) async fn handle_broadcast(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+) -> Result<(), BotError> {
+    let username = sender_username(&message)?;
+
+    if !is_admin(deps.admins_list.clone(), &username).await {
+        bot.send_message(message.chat.id, "Вы не находитесь в списке админов")
+            .await?;
+        return Ok(());
+    }
+
+    bot.send_message(message.chat.id, "Введите сообщение для рассылки")
+        .await?;
+
+    dialogue.update(State::Broadcast).await?;
+
+    Ok(())
+}
+
+pub(super) async fn callback_broadcast(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+) -> Result<(), BotError> {
+    let text = message_text(&message)?;
+
+    let chats = deps
+        .chat_registry
+        .lock()
+        .await
+        .values()
+        .copied()
+        .collect::<HashSet<_>>();
+
+    let (mut sent, mut failed) = (0, 0);
+
+    for chat_id in chats {
+        match bot.send_message(chat_id, text).await {
+            Ok(_) => sent += 1,
+            Err(e) => {
+                tracing::warn!(error = %e, chat_id = %chat_id, "broadcast delivery failed");
+                failed += 1;
+            }
+        }
+    }
+
+    bot.send_message(
+        message.chat.id,
+        format!("Отправлено: {}, Ошибок: {}", sent, failed),
+    )
+    .await?;
+
+    dialogue.update(State::None).await?;
+
+    handle_start(bot, message, dialogue).await
+}
+
+//
+
+pub(super) async fn handle_admin(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    allowed_list: PersistentUserList,
+    admins_list: SharedAdmins,
+) -> Result<(), BotError> {
+    let username = sender_username(&message)?;
+
+    if !is_admin(admins_list.clone(), &username).await {
+        bot.send_message(message.chat.id, "Вы не находитесь в списке админов")
+            .await?;
+        handle_start(bot, message, dialogue).await?;
+        return Ok(());
+    };
+
+    let keyboard = build_admin_keyboard(allowed_list.list().await.len());
+
+    bot.send_message(message.chat.id, "Выберите опцию")
+        .reply_markup(keyboard)
+        .await?;
+
+    dialogue.update(State::Admin).await?;
+
+    Ok(())
+}
+
+pub(super) async fn callback_admin(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    allowed_list: PersistentUserList,
+    admins_list: SharedAdmins,
+) -> Result<(), BotError> {
+    if let Some(text) = message.text() {
+        match text {
+            "Добавить пользователя" => {
+                handle_add_user(bot, message, dialogue).await?
+            }
+
+            "Удалить пользователя" => {
+                handle_delete_user(bot, message, allowed_list, dialogue).await?
+            }
+
+            text if text.starts_with(ADMIN_LIST_USERS) => {
+                handle_list_users(bot, message, dialogue, allowed_list).await?
+            }
+
+            "Список админов" => {
+                handle_list_admins(bot, message, dialogue, admins_list).await?
+            }
+
+            "Назад" => handle_start(bot, message, dialogue).await?,
+
+            _ => {}
+        };
+    }
+
+    Ok(())
+}
+
+//
+
+async fn handle_add_user(bot: Bot, message: Message, dialogue: MyDialogue) -> Result<(), BotError> {
+    bot.send_message(
+        message.chat.id,
+        "Введите имя пользователя или «Отмена» для возврата",
+    )
+    .await?;
+
+    dialogue.update(State::AddUser).await?;
+
+    Ok(())
+}
+
+// Pasted names often come with stray whitespace or extra lines
+fn parse_username(text: &str) -> &str {
+    let first_line = text.lines().next().unwrap_or("");
+    normalize_username(first_line.trim()).trim()
+}
+
+// Cyrillic isn't covered by eq_ignore_ascii_case, hence the lowercasing
+fn is_cancel(text: &str) -> bool {
+    let text = text.trim();
+    text.to_lowercase() == "отмена" || text == "/cancel"
+}
+
+pub(super) async fn callback_add_user(
+    bot: Bot,
+    message: Message,
+    allowed_list: PersistentUserList,
+    dialogue: MyDialogue,
+    admins_list: SharedAdmins,
+) -> Result<(), BotError> {
+    let stripped = parse_username(message_text(&message)?);
+
+    if is_cancel(stripped) {
+        dialogue.update(State::None).await?;
+        return handle_admin(bot, message, dialogue, allowed_list, admins_list).await;
+    }
+
+    if stripped.is_empty() {
+        bot.send_message(message.chat.id, "Вы не ввели имя пользователя.")
+            .await?;
+        return Ok(());
+    }
+
+    allowed_list.add(stripped).await?;
+
+    dialogue.update(State::None).await?;
+
+    let text = format!("Пользователь @{} успешно добавлен", stripped);
+
+    bot.send_message(message.chat.id, text).await?;
+
+    handle_start(bot, message, dialogue).await?;
+
+    Ok(())
+}
+
+//
+
+async fn handle_delete_user(
+    bot: Bot,
+    message: Message,
+    allowed_list: PersistentUserList,
+    dialogue: MyDialogue,
+) -> Result<(), BotError> {
+    let accounts = allowed_list.list().await;
+
+    let keyboard = build_delete_user_keyboard(&accounts);
+
+    let text = "Выберите аккаунт для удаления".to_string();
+
+    bot.send_message(message.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .reply_markup(keyboard)
+        .await?;
+
+    dialogue.update(State::DeleteUser).await?;
+
+    Ok(())
+}
+
+pub(super) async fn callback_delete_user(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    allowed: PersistentUserList,
+    admins_list: SharedAdmins,
+) -> Result<(), BotError> {
+    let text = message_text(&message)?;
+
+    if text == CANCEL {
+        return handle_admin(bot, message, dialogue, allowed, admins_list).await;
+    }
+
+    let data = normalize_username(text).to_string();
+
+    // Remember the entry as it is stored, which may still carry the '@'
+    let stored = allowed
+        .list()
+        .await
+        .into_iter()
+        .find(|user| normalize_username(user) == data);
+
+    let Some(username) = stored else {
+        bot.send_message(message.chat.id, "Пользователь не найден")
+            .await?;
+        return handle_admin(bot, message, dialogue, allowed, admins_list).await;
+    };
+
+    let text = format!("Удалить пользователя @{}?", data);
+
+    bot.send_message(message.chat.id, text)
+        .reply_markup(build_delete_user_confirm_keyboard(&data))
+        .await?;
+
+    dialogue
+        .update(State::DeleteUserConfirm { username })
+        .await?;
+
+    Ok(())
+}
+
+pub(super) async fn callback_delete_user_confirm(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    allowed: PersistentUserList,
+    admins_list: SharedAdmins,
+    username: String,
+) -> Result<(), BotError> {
+    let text = message_text(&message)?;
+    let display_name = normalize_username(&username);
+
+    if text == DELETE_USER_CANCEL {
+        return handle_admin(bot, message, dialogue, allowed, admins_list).await;
+    }
+
+    if text != delete_user_confirm_label(display_name) {
+        bot.send_message(message.chat.id, "Выберите вариант на клавиатуре")
+            .await?;
+        return Ok(());
+    }
+
+    if allowed.remove(&username).await? {
+        let text = format!("Пользователь @{} успешно удалён", display_name);
+        bot.send_message(message.chat.id, text).await?;
+    }
+
+    dialogue.update(State::None).await?;
+
+    if let Err(e) = handle_start(bot, message, dialogue).await {
+        tracing::error!(error = %e, "handler error");
+    }
+
+    Ok(())
+}
+
+//
+
+async fn handle_list_users(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    allowed_list: PersistentUserList,
+) -> Result<(), BotError> {
+    let list = allowed_list
+        .list()
+        .await
+        .iter()
+        .map(|nickname| format!("@{nickname}"))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let text = format!("Список пользователей:\n{}", list);
+
+    for chunk in split_message(&text, TELEGRAM_MAX_MESSAGE_LEN) {
+        bot.send_message(message.chat.id, chunk).await?;
+    }
+
+    handle_start(bot, message, dialogue).await?;
+
+    Ok(())
+}
+
+pub(super) async fn handle_reload(
+    bot: Bot,
+    message: Message,
+    deps: DependenciesForDispatcher,
+) -> Result<(), BotError> {
+    let username = sender_username(&message)?;
+
+    if !is_admin(deps.admins_list.clone(), &username).await {
+        bot.send_message(message.chat.id, "Вы не находитесь в списке админов")
+            .await?;
+        return Ok(());
+    }
+
+    let mut telegram_config: TgCfg = read_to_struct(TG_CFG_PATH).await?;
+
+    // Validated the same way as at startup, so a broken file can't lock the admins out
+    env_override(&mut telegram_config.token, "IIKO_BOT_TOKEN");
+
+    if let Err(errors) = telegram_config.validate() {
+        bot.send_message(
+            message.chat.id,
+            format!("Конфигурация не перезагружена:\n- {}", errors.join("\n- ")),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let (users, admins) = (telegram_config.accounts.len(), telegram_config.admins.len());
+
+    {
+        let mut admins_list = deps.admins_list.write().await;
+        deps.allowed_list.replace(telegram_config.accounts).await;
+        *admins_list = telegram_config.admins;
+    }
+
+    tracing::info!(users, admins, "configuration reloaded");
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Конфигурация перезагружена: {} пользователей, {} админов",
+            users, admins
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_list_admins(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    admins_list: SharedAdmins,
+) -> Result<(), BotError> {
+    let list = admins_list
+        .read()
+        .await
+        .iter()
+        .map(|nickname| format!("@{nickname}"))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let text = format!("Список админов:\n{}", list);
+
+    for chunk in split_message(&text, TELEGRAM_MAX_MESSAGE_LEN) {
+        bot.send_message(message.chat.id, chunk).await?;
+    }
+
+    handle_start(bot, message, dialogue).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_username_takes_the_trimmed_first_line() {
+        assert_eq!(parse_username(" @username\nextra "), "username");
+        assert_eq!(parse_username("username"), "username");
+        assert_eq!(parse_username("@ username "), "username");
+        assert_eq!(parse_username("\n"), "");
+    }
+
+    #[test]
+    fn is_cancel_ignores_case_and_whitespace() {
+        for text in ["Отмена", "отмена", "ОТМЕНА", " Отмена ", "/cancel"] {
+            assert!(is_cancel(text), "{text}");
+        }
+    }
+
+    #[test]
+    fn is_cancel_rejects_usernames() {
+        for text in ["otmena", "username", "/cancelled", ""] {
+            assert!(!is_cancel(text), "{text}");
+        }
+    }
+}
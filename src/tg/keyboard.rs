@@ -0,0 +1,323 @@
+use crate::olap::{OlapMap, SortOrder, olap_page_count};
+
+//
+
+use std::vec;
+
+//
+
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, KeyboardButton, KeyboardMarkup};
+
+//
+
+use super::{
+    ADMIN_LIST_USERS, CANCEL, DELETE_USER_CANCEL, OLAP_CATEGORIES_PER_PAGE, OLAP_DISH_NEXT_PAGE,
+    OLAP_DISH_PREV_PAGE, OLAP_DISH_SORT, OLAP_NEXT_PAGE, OLAP_PERIODS, OLAP_PRESETS,
+    OLAP_PREV_PAGE, OLAP_REPORT_TYPES,
+};
+
+//
+
+// Lays buttons out two per row
+fn paired_rows<S: AsRef<str>>(labels: &[S]) -> Vec<Vec<KeyboardButton>> {
+    labels
+        .chunks(2)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|label| KeyboardButton::new(label.as_ref()))
+                .collect()
+        })
+        .collect()
+}
+
+pub(super) fn build_main_keyboard() -> KeyboardMarkup {
+    KeyboardMarkup::default()
+        .append_row(vec![
+            KeyboardButton::new("Отчёты"),
+            KeyboardButton::new("Сменить сервер"),
+        ])
+        .append_row(vec![KeyboardButton::new("Администрирование")])
+        .one_time_keyboard()
+}
+
+pub(super) fn build_reports_keyboard() -> KeyboardMarkup {
+    KeyboardMarkup::default()
+        .append_row(vec![
+            KeyboardButton::new("За сегодня"),
+            KeyboardButton::new("За вчера"),
+        ])
+        .append_row(vec![
+            KeyboardButton::new("За 7 дней"),
+            KeyboardButton::new("За текущий месяц"),
+            KeyboardButton::new("За прошлый месяц"),
+        ])
+        .append_row(vec![
+            KeyboardButton::new("Произвольный период"),
+            KeyboardButton::new("Произвольный период (N дней)"),
+        ])
+        .append_row(vec![
+            KeyboardButton::new("Сравнение дней"),
+            KeyboardButton::new("Olap отчёт"),
+        ])
+        .append_row(vec![
+            KeyboardButton::new("Сравнение месяцев"),
+            KeyboardButton::new("Последние смены"),
+            KeyboardButton::new("За текущий квартал"),
+        ])
+        .append_row(vec![KeyboardButton::new("Назад")])
+        .one_time_keyboard()
+}
+
+pub(super) fn build_admin_keyboard(user_count: usize) -> KeyboardMarkup {
+    KeyboardMarkup::default()
+        .append_row(vec![
+            KeyboardButton::new("Добавить пользователя"),
+            KeyboardButton::new("Удалить пользователя"),
+        ])
+        .append_row(vec![
+            KeyboardButton::new(format!("{} ({})", ADMIN_LIST_USERS, user_count)),
+            KeyboardButton::new("Список админов"),
+        ])
+        .append_row(vec![KeyboardButton::new("Назад")])
+        .one_time_keyboard()
+}
+
+// "Отмена" gets a row of its own so it stays last however many servers there are
+pub(super) fn build_switch_keyboard(server_keys: &[String]) -> KeyboardMarkup {
+    KeyboardMarkup::new(paired_rows(server_keys))
+        .append_row(vec![KeyboardButton::new(CANCEL)])
+        .one_time_keyboard()
+}
+
+pub(super) fn build_latest_shifts_keyboard(counts: &[usize]) -> KeyboardMarkup {
+    KeyboardMarkup::default()
+        .append_row(
+            counts
+                .iter()
+                .map(|count| KeyboardButton::new(count.to_string()))
+                .collect::<Vec<_>>(),
+        )
+        .append_row(vec![KeyboardButton::new("Назад")])
+        .one_time_keyboard()
+}
+
+pub(super) fn build_delete_user_keyboard(accounts: &[String]) -> KeyboardMarkup {
+    KeyboardMarkup::new(paired_rows(accounts))
+        .append_row(vec![KeyboardButton::new(CANCEL)])
+        .one_time_keyboard()
+}
+
+pub(super) fn build_delete_user_confirm_keyboard(username: &str) -> KeyboardMarkup {
+    KeyboardMarkup::default()
+        .append_row(vec![
+            KeyboardButton::new(delete_user_confirm_label(username)),
+            KeyboardButton::new(DELETE_USER_CANCEL),
+        ])
+        .one_time_keyboard()
+}
+
+pub(super) fn delete_user_confirm_label(username: &str) -> String {
+    format!("✅ Удалить @{}", username)
+}
+
+pub(super) fn build_olap_category_keyboard(categories: &[&str]) -> KeyboardMarkup {
+    KeyboardMarkup::new(paired_rows(categories)).one_time_keyboard()
+}
+
+pub(super) fn build_olap_report_type_keyboard() -> KeyboardMarkup {
+    let labels = OLAP_REPORT_TYPES.map(|report_type| report_type.description());
+
+    KeyboardMarkup::new(paired_rows(&labels))
+        .append_row(vec![KeyboardButton::new("Быстрые отчёты")])
+        .append_row(vec![KeyboardButton::new("Назад")])
+        .one_time_keyboard()
+}
+
+// Preset names are long, so each gets a row of its own
+pub(super) fn build_olap_presets_keyboard() -> KeyboardMarkup {
+    OLAP_PRESETS
+        .iter()
+        .fold(KeyboardMarkup::default(), |keyboard, (label, _)| {
+            keyboard.append_row(vec![KeyboardButton::new(*label)])
+        })
+        .append_row(vec![KeyboardButton::new("Назад")])
+        .one_time_keyboard()
+}
+
+pub(super) fn build_olap_period_keyboard() -> KeyboardMarkup {
+    let labels = OLAP_PERIODS.map(|(label, _)| label);
+
+    KeyboardMarkup::new(paired_rows(&labels))
+        .append_row(vec![KeyboardButton::new("Назад")])
+        .one_time_keyboard()
+}
+
+pub(super) fn olap_category_keyboard(olap: &OlapMap, page: usize) -> (KeyboardMarkup, usize) {
+    let mut categories: Vec<&str> = olap.keys().map(String::as_str).collect();
+    categories.sort();
+
+    let pages = olap_page_count(categories.len(), OLAP_CATEGORIES_PER_PAGE);
+    let page = page.min(pages - 1);
+
+    let start = (page * OLAP_CATEGORIES_PER_PAGE).min(categories.len());
+    let end = (start + OLAP_CATEGORIES_PER_PAGE).min(categories.len());
+
+    let mut keyboard = build_olap_category_keyboard(&categories[start..end]);
+
+    let mut navigation = Vec::new();
+
+    if page > 0 {
+        navigation.push(KeyboardButton::new(OLAP_PREV_PAGE));
+    }
+
+    if page + 1 < pages {
+        navigation.push(KeyboardButton::new(OLAP_NEXT_PAGE));
+    }
+
+    if !navigation.is_empty() {
+        keyboard = keyboard.append_row(navigation);
+    }
+
+    (keyboard, pages)
+}
+
+//
+
+pub(super) fn olap_dish_page_keyboard(
+    page: usize,
+    pages: usize,
+    sort: SortOrder,
+) -> InlineKeyboardMarkup {
+    let mut row = Vec::new();
+
+    if page > 0 {
+        row.push(InlineKeyboardButton::callback(
+            "Предыдущая страница",
+            OLAP_DISH_PREV_PAGE,
+        ));
+    }
+
+    if page + 1 < pages {
+        row.push(InlineKeyboardButton::callback(
+            "Следующая страница",
+            OLAP_DISH_NEXT_PAGE,
+        ));
+    }
+
+    let sort_row = vec![InlineKeyboardButton::callback(
+        format!("Сортировка: {}", sort.label()),
+        OLAP_DISH_SORT,
+    )];
+
+    let rows = if row.is_empty() {
+        vec![sort_row]
+    } else {
+        vec![row, sort_row]
+    };
+
+    InlineKeyboardMarkup::new(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(keyboard: &KeyboardMarkup) -> Vec<Vec<&str>> {
+        keyboard
+            .keyboard
+            .iter()
+            .map(|row| row.iter().map(|button| button.text.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn main_keyboard_layout() {
+        assert_eq!(
+            labels(&build_main_keyboard()),
+            vec![vec!["Отчёты", "Сменить сервер"], vec!["Администрирование"]]
+        );
+    }
+
+    #[test]
+    fn reports_keyboard_ends_with_back() {
+        let keyboard = build_reports_keyboard();
+        let rows = labels(&keyboard);
+
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0], vec!["За сегодня", "За вчера"]);
+        assert_eq!(rows.last().unwrap(), &vec!["Назад"]);
+    }
+
+    #[test]
+    fn admin_keyboard_shows_the_user_count() {
+        let keyboard = build_admin_keyboard(3);
+        let rows = labels(&keyboard);
+
+        assert_eq!(
+            rows[0],
+            vec!["Добавить пользователя", "Удалить пользователя"]
+        );
+        assert_eq!(rows[1][0], format!("{} (3)", ADMIN_LIST_USERS));
+        assert_eq!(rows[2], vec!["Назад"]);
+    }
+
+    #[test]
+    fn switch_keyboard_pairs_servers_and_ends_with_cancel() {
+        let servers = ["a", "b", "c"].map(String::from);
+
+        assert_eq!(
+            labels(&build_switch_keyboard(&servers)),
+            vec![vec!["a", "b"], vec!["c"], vec![CANCEL]]
+        );
+    }
+
+    #[test]
+    fn olap_category_keyboard_pairs_categories() {
+        assert_eq!(
+            labels(&build_olap_category_keyboard(&["Бар", "Кухня", "Десерты"])),
+            vec![vec!["Бар", "Кухня"], vec!["Десерты"]]
+        );
+    }
+
+    #[test]
+    fn latest_shifts_keyboard_layout() {
+        assert_eq!(
+            labels(&build_latest_shifts_keyboard(&[3, 5, 10])),
+            vec![vec!["3", "5", "10"], vec!["Назад"]]
+        );
+    }
+
+    #[test]
+    fn olap_report_type_keyboard_pairs_the_types() {
+        let keyboard = build_olap_report_type_keyboard();
+        let rows = labels(&keyboard);
+
+        assert_eq!(rows.len(), OLAP_REPORT_TYPES.len().div_ceil(2) + 2);
+        assert!(rows.iter().all(|row| row.len() <= 2));
+        assert_eq!(rows[rows.len() - 2], vec!["Быстрые отчёты"]);
+        assert_eq!(rows.last().unwrap(), &vec!["Назад"]);
+    }
+
+    #[test]
+    fn olap_presets_keyboard_has_one_preset_per_row() {
+        let keyboard = build_olap_presets_keyboard();
+        let rows = labels(&keyboard);
+
+        assert_eq!(rows.len(), OLAP_PRESETS.len() + 1);
+        for (row, (label, _)) in rows.iter().zip(OLAP_PRESETS.iter()) {
+            assert_eq!(row, &vec![*label]);
+        }
+        assert_eq!(rows.last().unwrap(), &vec!["Назад"]);
+    }
+
+    #[test]
+    fn olap_period_keyboard_pairs_the_periods() {
+        let keyboard = build_olap_period_keyboard();
+        let rows = labels(&keyboard);
+
+        assert_eq!(rows.len(), OLAP_PERIODS.len().div_ceil(2) + 1);
+        assert_eq!(rows[0], vec![OLAP_PERIODS[0].0, OLAP_PERIODS[1].0]);
+        assert_eq!(rows.last().unwrap(), &vec!["Назад"]);
+    }
+}
@@ -0,0 +1,1560 @@
+use crate::cache::ReportCache;
+use crate::date::{moscow_now, moscow_time, until_next_moscow};
+use crate::error::BotError;
+use crate::format::{format_ruble, set_currency_symbol, set_thousand_separator};
+use crate::iiko::{Dates, GetShifts, Server};
+use crate::olap::{OlapMap, PeriodType, ReportConfig, ReportType, SortOrder};
+use crate::rate_limit::RateLimiter;
+use crate::server_pool::ServerPool;
+use crate::shared::{ServerConfig, read_to_struct, write_struct};
+use crate::users::{PersistentUserList, normalize_username};
+
+//
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use std::vec;
+use std::{error::Error, sync::Arc};
+
+//
+
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+
+//
+
+use teloxide::dispatching::dialogue::InMemStorage;
+use teloxide::dispatching::{HandlerExt, UpdateFilterExt};
+use teloxide::payloads::{SendMessageSetters, SetChatMenuButtonSetters};
+use teloxide::prelude::{Dialogue, Dispatcher, Request, Requester, ResponseResult};
+use teloxide::types::{BotCommand, CallbackQuery, ChatId, Me, Update};
+use teloxide::{Bot, dptree};
+use teloxide::{
+    types::{Message, ParseMode},
+    utils::command::BotCommands,
+    utils::markdown::escape,
+};
+
+//
+
+use tokio::sync::{Mutex, RwLock};
+use tokio_util::sync::CancellationToken;
+
+//
+
+mod admin;
+mod keyboard;
+mod reports;
+mod server;
+
+use keyboard::build_main_keyboard;
+
+use admin::{
+    callback_add_user, callback_admin, callback_broadcast, callback_delete_user,
+    callback_delete_user_confirm, handle_admin, handle_broadcast, handle_reload,
+};
+use reports::{
+    callback_days_input, callback_latest_shifts, callback_olap, callback_olap_dish_page,
+    callback_olap_period, callback_olap_preset, callback_olap_report_type, callback_reports,
+    handle_custom_date_end, handle_custom_date_start, handle_days_input, handle_export_csv,
+    handle_export_json, handle_last_n_days, handle_reports, parse_days,
+};
+use server::{
+    callback_add_server_name, callback_add_server_url, callback_remove_server, callback_switch,
+    handle_add_server, handle_ping, handle_remove_server, handle_status, handle_switch,
+};
+
+//
+
+type SharedOlap = Arc<Mutex<HashMap<ChatId, CachedOlap>>>;
+// Every report reads the registry while only switching, adding and removing
+// servers write to it, so readers must never block each other.
+type SharedServer = Arc<RwLock<ServerRegistry>>;
+type MyDialogue = Dialogue<State, InMemStorage<State>>;
+type DialogueTimeouts = Arc<Mutex<HashMap<ChatId, CancellationToken>>>;
+type SharedAdmins = Arc<RwLock<Vec<String>>>;
+type SharedChatRegistry = Arc<Mutex<HashMap<String, ChatId>>>;
+type SharedSortOrders = Arc<Mutex<HashMap<ChatId, SortOrder>>>;
+type OlapPreset = fn(bool) -> ReportConfig;
+
+//
+
+const TG_CFG_PATH: &str = "/etc/iiko-bot/tg_cfg.toml";
+const CFG_PATH: &str = "/etc/iiko-bot/cfg.toml";
+const CHAT_REGISTRY_PATH: &str = "/etc/iiko-bot/chat_registry.toml";
+
+const TELEGRAM_MAX_MESSAGE_LEN: usize = 4096;
+const OLAP_CATEGORIES_PER_PAGE: usize = 6;
+const OLAP_PREV_PAGE: &str = "← Назад";
+const OLAP_NEXT_PAGE: &str = "Далее →";
+const OLAP_DISHES_PER_PAGE: usize = 20;
+const OLAP_REPORT_TYPES: [ReportType; 4] = [
+    ReportType::SALES,
+    ReportType::DELIVERIES,
+    ReportType::WRITEOFFS,
+    ReportType::TRANSACTIONS,
+];
+const OLAP_PRESETS: [(&str, OlapPreset); 3] = [
+    ("Продажи по категориям", ReportConfig::sales_by_category),
+    ("Продажи по блюдам", ReportConfig::sales_by_dish),
+    (
+        "Списания по категориям",
+        ReportConfig::writeoffs_by_category,
+    ),
+];
+const OLAP_PERIODS: [(&str, PeriodType); 6] = [
+    ("Сегодня", PeriodType::TODAY),
+    ("Вчера", PeriodType::YESTERDAY),
+    ("Текущая неделя", PeriodType::CURRENT_WEEK),
+    ("Прошлая неделя", PeriodType::PREVIOUS_WEEK),
+    ("Текущий месяц", PeriodType::CURRENT_MONTH),
+    ("Прошлый месяц", PeriodType::PREVIOUS_MONTH),
+];
+const OLAP_DISH_PREV_PAGE: &str = "olap_dish_prev";
+const OLAP_DISH_NEXT_PAGE: &str = "olap_dish_next";
+const OLAP_DISH_SORT: &str = "olap_dish_sort";
+// The admin keyboard appends the user count to this label
+const ADMIN_LIST_USERS: &str = "Список пользователей";
+const DELETE_USER_CANCEL: &str = "❌ Отмена";
+const CANCEL: &str = "Отмена";
+
+//
+
+#[derive(Clone)]
+struct CachedOlap {
+    olap: OlapMap,
+    fetched: Instant,
+    form: ReportConfig,
+}
+
+//
+
+#[derive(Deserialize, Clone)]
+struct Cfg {
+    #[serde(default)]
+    login: String,
+    #[serde(default)]
+    pass: String,
+    servers: HashMap<String, ServerConfig>,
+    #[serde(default = "default_token_refresh_margin_secs")]
+    token_refresh_margin_secs: u64,
+    #[serde(default = "default_auth_hash_algo")]
+    auth_hash_algo: String,
+    #[serde(default = "default_olap_cache_ttl_secs")]
+    olap_cache_ttl_secs: u64,
+    #[serde(default)]
+    olap_exclude_deleted: bool,
+    olap_min_revenue: Option<f64>,
+    #[serde(default = "default_thousand_separator")]
+    thousand_separator: char,
+    #[serde(default = "default_currency_symbol")]
+    currency_symbol: String,
+    #[serde(default = "default_state_file")]
+    state_file: String,
+    #[serde(default = "default_report_cache_ttl_secs")]
+    report_cache_ttl_secs: u64,
+    daily_report_time: Option<String>,
+    min_revenue_alert: Option<f64>,
+    #[serde(default = "default_alert_check_interval_secs")]
+    alert_check_interval_secs: u64,
+    max_shift_hours: Option<u64>,
+    #[serde(default = "default_connect_timeout_secs")]
+    connect_timeout_secs: u64,
+    #[serde(default = "default_read_timeout_secs")]
+    read_timeout_secs: u64,
+    #[serde(default = "default_olap_timeout_secs")]
+    olap_timeout_secs: u64,
+    #[serde(default = "default_circuit_breaker_threshold")]
+    circuit_breaker_threshold: u32,
+    #[serde(default = "default_circuit_open_secs")]
+    circuit_open_secs: u64,
+}
+
+fn default_token_refresh_margin_secs() -> u64 {
+    300
+}
+
+fn default_auth_hash_algo() -> String {
+    "sha256".into()
+}
+
+fn default_olap_cache_ttl_secs() -> u64 {
+    300
+}
+
+fn default_thousand_separator() -> char {
+    '\u{00A0}'
+}
+
+fn default_currency_symbol() -> String {
+    "₽".into()
+}
+
+fn default_state_file() -> String {
+    "/etc/iiko-bot/state.toml".into()
+}
+
+fn default_report_cache_ttl_secs() -> u64 {
+    60
+}
+
+fn default_alert_check_interval_secs() -> u64 {
+    600
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    3
+}
+
+fn default_read_timeout_secs() -> u64 {
+    30
+}
+
+fn default_olap_timeout_secs() -> u64 {
+    60
+}
+
+fn default_circuit_breaker_threshold() -> u32 {
+    5
+}
+
+fn default_circuit_open_secs() -> u64 {
+    60
+}
+
+impl Cfg {
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if self.login.trim().is_empty() {
+            errors.push("cfg.toml: пустой login".to_string());
+        }
+
+        if self.pass.is_empty() {
+            errors.push("cfg.toml: пустой pass".to_string());
+        }
+
+        if self.servers.is_empty() {
+            errors.push("cfg.toml: список серверов пуст".to_string());
+        }
+
+        for (name, server) in &self.servers {
+            let url = &server.url;
+
+            if url.is_empty() || url.chars().any(char::is_whitespace) {
+                errors.push(format!(
+                    "cfg.toml: некорректный адрес сервера {name}: '{url}'"
+                ));
+            }
+        }
+
+        if let Some(time) = &self.daily_report_time
+            && parse_report_time(time).is_none()
+        {
+            errors.push(format!(
+                "cfg.toml: некорректное время ежедневного отчёта: '{time}'"
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn connect(&self, server: &ServerConfig) -> Server {
+        Server::new(self.login.clone(), self.pass.clone(), server.clone())
+            .with_refresh_margin(Duration::from_secs(self.token_refresh_margin_secs))
+            .with_hash_algo(self.auth_hash_algo.clone())
+            .with_timeouts(
+                Duration::from_secs(self.connect_timeout_secs),
+                Duration::from_secs(self.read_timeout_secs),
+            )
+            .with_olap_timeout(Duration::from_secs(self.olap_timeout_secs))
+            .with_circuit_breaker(
+                self.circuit_breaker_threshold,
+                Duration::from_secs(self.circuit_open_secs),
+            )
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct TgCfg {
+    #[serde(default)]
+    token: String,
+    accounts: Vec<String>,
+    admins: Vec<String>,
+    #[serde(default = "default_dialogue_timeout_secs")]
+    dialogue_timeout_secs: u64,
+    #[serde(default = "default_max_requests_per_minute")]
+    max_requests_per_minute: u32,
+    #[serde(default)]
+    daily_report_chat_ids: Vec<i64>,
+}
+
+impl TgCfg {
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if self.token.trim().is_empty() {
+            errors.push("tg_cfg.toml: пустой token".to_string());
+        }
+
+        if self.admins.is_empty() {
+            errors.push("tg_cfg.toml: список админов пуст".to_string());
+        }
+
+        let mut seen = HashSet::new();
+
+        for account in &self.accounts {
+            if !seen.insert(normalize_username(account)) {
+                errors.push(format!("tg_cfg.toml: пользователь {account} указан дважды"));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn env_override(field: &mut String, var: &str) {
+    if let Ok(value) = std::env::var(var) {
+        *field = value;
+        tracing::debug!(var, "config field overridden from environment");
+    }
+}
+
+fn parse_report_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M").ok()
+}
+
+fn default_dialogue_timeout_secs() -> u64 {
+    300
+}
+
+fn default_max_requests_per_minute() -> u32 {
+    30
+}
+
+struct ServerState {
+    map: HashMap<String, ServerConfig>,
+    current: String,
+}
+
+impl ServerState {
+    fn new(map: HashMap<String, ServerConfig>) -> Result<Self, String> {
+        let Some(current) = map.keys().min().cloned() else {
+            return Err("No servers configured".to_string());
+        };
+
+        Ok(Self { map, current })
+    }
+}
+
+// The server list every new chat starts from, plus each chat's own view of it.
+struct ServerRegistry {
+    servers: HashMap<String, ServerConfig>,
+    chats: HashMap<ChatId, ServerState>,
+    current: Option<String>,
+}
+
+impl ServerRegistry {
+    fn new(
+        servers: HashMap<String, ServerConfig>,
+        current: Option<String>,
+    ) -> Result<Self, String> {
+        let ServerState {
+            map: servers,
+            current: first,
+        } = ServerState::new(servers)?;
+
+        Ok(Self {
+            servers,
+            chats: HashMap::new(),
+            current: current.or(Some(first)),
+        })
+    }
+
+    // What a chat that never switched servers would get from chat_server_state
+    fn default_current(&self) -> String {
+        self.current
+            .clone()
+            .filter(|current| self.servers.contains_key(current))
+            .or_else(|| self.servers.keys().min().cloned())
+            .unwrap_or_default()
+    }
+
+    fn chat_servers(&self, chat_id: ChatId) -> &HashMap<String, ServerConfig> {
+        self.chats
+            .get(&chat_id)
+            .map_or(&self.servers, |state| &state.map)
+    }
+
+    fn chat_current(&self, chat_id: ChatId) -> String {
+        self.chats
+            .get(&chat_id)
+            .map_or_else(|| self.default_current(), |state| state.current.clone())
+    }
+
+    // None when the name was mistyped or the server removed for this chat
+    fn chat_server(&self, chat_id: ChatId, name: &str) -> Option<ServerConfig> {
+        self.chat_servers(chat_id).get(name).cloned()
+    }
+
+    fn restore_chats(&mut self, chats: HashMap<String, String>) {
+        for (chat_id, current) in chats {
+            let Ok(id) = chat_id.parse::<i64>() else {
+                tracing::warn!(chat_id, "saved chat id is not a number");
+                continue;
+            };
+
+            if !self.servers.contains_key(&current) {
+                tracing::warn!(
+                    chat_id,
+                    server = current,
+                    "saved server is not in the config"
+                );
+                continue;
+            }
+
+            self.chats.insert(
+                ChatId(id),
+                ServerState {
+                    map: self.servers.clone(),
+                    current,
+                },
+            );
+        }
+    }
+
+    fn persisted(&self) -> PersistedState {
+        PersistedState {
+            current_server: self.current.clone(),
+            chats: self
+                .chats
+                .iter()
+                .map(|(chat_id, state)| (chat_id.to_string(), state.current.clone()))
+                .collect(),
+        }
+    }
+}
+
+// current_server is the startup default for chats that never switched; each
+// chat's own choice lives in chats, keyed by chat id.
+#[derive(Deserialize, Serialize, Default)]
+struct PersistedState {
+    #[serde(default)]
+    current_server: Option<String>,
+    #[serde(default)]
+    chats: HashMap<String, String>,
+}
+
+#[derive(Clone)]
+struct DependenciesForDispatcher {
+    config: Arc<Cfg>,
+    allowed_list: PersistentUserList,
+    admins_list: SharedAdmins,
+    servers: SharedServer,
+    olap_store: SharedOlap,
+    sort_orders: SharedSortOrders,
+    chat_registry: SharedChatRegistry,
+    rate_limiter: RateLimiter,
+    report_cache: ReportCache,
+    pool: ServerPool,
+    background_tasks: CancellationToken,
+    max_requests_per_minute: u32,
+    timeouts: DialogueTimeouts,
+    dialogue_timeout: Duration,
+}
+
+//
+
+#[derive(BotCommands, Clone)]
+#[command(rename_rule = "lowercase", description = "Поддерживаемые команды:")]
+enum Command {
+    #[command(description = "Запустить бота")]
+    Start,
+    #[command(description = "Выручка за последние N дней, например /lastn 14")]
+    Lastn(String),
+    #[command(description = "Отменить текущее действие")]
+    Cancel,
+    #[command(description = "Проверить задержку до серверов")]
+    Ping,
+    #[command(description = "Состояние всех серверов")]
+    Status,
+    #[command(description = "Выгрузить смены за 30 дней в JSON")]
+    Exportjson,
+    #[command(description = "Выгрузить смены в CSV, например /exportcsv 30")]
+    Exportcsv(String),
+    #[command(description = "Перечитать конфигурацию (только для админов)")]
+    Reload,
+    #[command(description = "Добавить сервер: /addserver имя адрес (только для админов)")]
+    Addserver(String),
+    #[command(description = "Удалить сервер (только для админов)")]
+    Removeserver,
+    #[command(description = "Разослать сообщение всем пользователям (только для админов)")]
+    Broadcast,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+enum State {
+    #[default]
+    None,
+    Switch,
+    OlapReportType,
+    OlapPreset,
+    OlapPeriod {
+        report_type: ReportType,
+    },
+    OlapPage(usize),
+    OlapDishPage {
+        category: String,
+        page: usize,
+    },
+    AddUser,
+    DeleteUser,
+    DeleteUserConfirm {
+        username: String,
+    },
+    Dialogue,
+    Report,
+    CustomDateStart,
+    CustomDateEnd {
+        from: String,
+    },
+    DaysInput,
+    LatestShiftsCount,
+    Admin,
+    AddServerName,
+    AddServerUrl {
+        name: String,
+    },
+    RemoveServerSelect,
+    Broadcast,
+}
+
+//
+
+fn chat_server_state(registry: &mut ServerRegistry, chat_id: ChatId) -> &mut ServerState {
+    let current = registry.default_current();
+
+    registry
+        .chats
+        .entry(chat_id)
+        .or_insert_with(|| ServerState {
+            map: registry.servers.clone(),
+            current,
+        })
+}
+
+async fn load_persisted_state(config: &Cfg) -> PersistedState {
+    if !Path::new(&config.state_file).exists() {
+        return PersistedState::default();
+    }
+
+    let mut state: PersistedState = match read_to_struct(&config.state_file).await {
+        Ok(state) => state,
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to read saved bot state");
+            return PersistedState::default();
+        }
+    };
+
+    if let Some(current) = &state.current_server
+        && !config.servers.contains_key(current)
+    {
+        tracing::warn!(
+            server = current,
+            "saved current server is not in the config"
+        );
+        state.current_server = None;
+    }
+
+    state
+}
+
+// A chat's server may have been removed or renamed in the config since it
+// was chosen; the registry default stands in for it then.
+async fn collect_server_info(
+    servers: SharedServer,
+    chat_id: ChatId,
+) -> Result<(ServerConfig, String), BotError> {
+    let registry = servers.read().await;
+    let current = registry.chat_current(chat_id);
+
+    if let Some(server_config) = registry.chat_server(chat_id, &current) {
+        return Ok((server_config, current));
+    }
+
+    let default = registry.default_current();
+
+    tracing::warn!(
+        server = current,
+        fallback = default,
+        "chat server is missing"
+    );
+
+    registry
+        .servers
+        .get(&default)
+        .map(|server_config| (server_config.clone(), default.clone()))
+        .ok_or_else(|| BotError::NotFound("Сервер не найден".into()))
+}
+
+fn message_text(message: &Message) -> Result<&str, BotError> {
+    message
+        .text()
+        .ok_or_else(|| BotError::NotFound("Невозможно получить текст сообщения".into()))
+}
+
+fn sender_username(message: &Message) -> Result<String, BotError> {
+    message
+        .from
+        .as_ref()
+        .and_then(|user| user.username.clone())
+        .ok_or(BotError::Unauthorized)
+}
+
+async fn is_allowed(allowed_list: PersistentUserList, username: &str) -> bool {
+    let username = normalize_username(username);
+
+    allowed_list
+        .any(|user| normalize_username(user) == username)
+        .await
+}
+
+async fn authorized_username(
+    message: &Message,
+    deps: &DependenciesForDispatcher,
+) -> Option<String> {
+    let username = sender_username(message).ok()?;
+
+    is_authorized(&username, deps).await.then_some(username)
+}
+
+async fn is_authorized(username: &str, deps: &DependenciesForDispatcher) -> bool {
+    is_allowed(deps.allowed_list.clone(), username).await
+        || is_admin(deps.admins_list.clone(), username).await
+}
+
+async fn is_admin(admins_list: SharedAdmins, username: &str) -> bool {
+    let username = normalize_username(username);
+
+    admins_list
+        .read()
+        .await
+        .iter()
+        .any(|admin| normalize_username(admin) == username)
+}
+
+//
+
+pub async fn initialise() -> Result<(), Box<dyn Error>> {
+    let mut telegram_config: TgCfg = read_to_struct(TG_CFG_PATH).await?;
+    let mut main_config: Cfg = read_to_struct(CFG_PATH).await?;
+
+    // Secrets may come from the environment instead of the config files, so
+    // an empty field only counts as an error once these are applied.
+    env_override(&mut telegram_config.token, "IIKO_BOT_TOKEN");
+    env_override(&mut main_config.login, "IIKO_BOT_LOGIN");
+    env_override(&mut main_config.pass, "IIKO_BOT_PASS");
+
+    let errors: Vec<String> = [telegram_config.validate(), main_config.validate()]
+        .into_iter()
+        .filter_map(Result::err)
+        .flatten()
+        .collect();
+
+    if !errors.is_empty() {
+        return Err(format!("Ошибки в конфигурации:\n- {}", errors.join("\n- ")).into());
+    }
+
+    // Handlers receive the dependencies by value on every update, so the
+    // config is shared instead of cloned each time.
+    let main_config = Arc::new(main_config);
+
+    let daily_report_chat_ids = telegram_config.daily_report_chat_ids;
+
+    let (token, accounts, admins, dialogue_timeout, max_requests_per_minute) = (
+        telegram_config.token,
+        telegram_config.accounts,
+        telegram_config.admins,
+        Duration::from_secs(telegram_config.dialogue_timeout_secs),
+        telegram_config.max_requests_per_minute,
+    );
+
+    let allowed = PersistentUserList::new(accounts, TG_CFG_PATH);
+    let admins = Arc::new(RwLock::new(admins));
+
+    let olap_store: SharedOlap = Arc::new(Mutex::new(HashMap::new()));
+
+    let saved_state = load_persisted_state(&main_config).await;
+
+    let mut registry =
+        ServerRegistry::new(main_config.servers.clone(), saved_state.current_server)?;
+    registry.restore_chats(saved_state.chats);
+
+    let servers: SharedServer = Arc::new(RwLock::new(registry));
+
+    let bot = Bot::new(token);
+
+    let handler = dptree::entry()
+        .branch(
+            Update::filter_message()
+                .enter_dialogue::<Message, InMemStorage<State>, State>()
+                .endpoint(handle_states),
+        )
+        .branch(
+            Update::filter_callback_query()
+                .enter_dialogue::<CallbackQuery, InMemStorage<State>, State>()
+                .endpoint(handle_callback_query),
+        );
+
+    let deps = DependenciesForDispatcher {
+        config: Arc::clone(&main_config),
+        allowed_list: allowed.clone(),
+        admins_list: admins.clone(),
+        servers: servers.clone(),
+        olap_store: olap_store.clone(),
+        sort_orders: Arc::new(Mutex::new(HashMap::new())),
+        chat_registry: Arc::new(Mutex::new(load_chat_registry().await)),
+        rate_limiter: RateLimiter::new(),
+        report_cache: ReportCache::new(),
+        pool: ServerPool::new(),
+        background_tasks: CancellationToken::new(),
+        max_requests_per_minute,
+        timeouts: Arc::new(Mutex::new(HashMap::new())),
+        dialogue_timeout,
+    };
+
+    set_thousand_separator(main_config.thousand_separator);
+    set_currency_symbol(main_config.currency_symbol.clone());
+
+    deps.pool.spawn_refresher(deps.background_tasks.clone());
+
+    if let Some(time) = main_config
+        .daily_report_time
+        .as_deref()
+        .and_then(parse_report_time)
+        && !daily_report_chat_ids.is_empty()
+    {
+        spawn_daily_report(bot.clone(), deps.clone(), time, daily_report_chat_ids);
+    }
+
+    if let Some(threshold) = main_config.min_revenue_alert {
+        spawn_revenue_alert(bot.clone(), deps.clone(), threshold);
+    }
+
+    if let Some(max_hours) = main_config.max_shift_hours {
+        spawn_open_shift_alert(bot.clone(), deps.clone(), max_hours);
+    }
+
+    tracing::info!(servers = main_config.servers.len(), "bot started");
+
+    let mut dispatcher = Dispatcher::builder(bot.clone(), handler)
+        .dependencies(dptree::deps![
+            deps.clone(),
+            InMemStorage::<State>::new(),
+            State::None
+        ])
+        .build();
+
+    // Stopping through the token lets handlers already running finish their
+    // updates instead of being dropped halfway
+    let shutdown_token = dispatcher.shutdown_token();
+
+    tokio::spawn(async move {
+        shutdown_signal().await;
+
+        match shutdown_token.shutdown() {
+            Ok(stopped) => stopped.await,
+            Err(e) => tracing::warn!(error = %e, "dispatcher was not running at shutdown"),
+        }
+    });
+
+    dispatcher.dispatch().await;
+
+    shutdown(&deps).await;
+
+    Ok(())
+}
+
+fn spawn_daily_report(
+    bot: Bot,
+    deps: DependenciesForDispatcher,
+    time: NaiveTime,
+    chat_ids: Vec<i64>,
+) {
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = deps.background_tasks.cancelled() => break,
+                _ = tokio::time::sleep(until_next_moscow(time)) => {}
+            }
+
+            let text = daily_summary(&deps).await;
+
+            for &chat_id in &chat_ids {
+                if let Err(e) = bot
+                    .send_message(ChatId(chat_id), text.clone())
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await
+                {
+                    tracing::warn!(chat_id, error = %e, "failed to send daily report");
+                }
+            }
+        }
+    });
+}
+
+async fn daily_summary(deps: &DependenciesForDispatcher) -> String {
+    let mut servers: Vec<(String, ServerConfig)> = deps
+        .servers
+        .read()
+        .await
+        .servers
+        .iter()
+        .map(|(name, server)| (name.clone(), server.clone()))
+        .collect();
+
+    servers.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let now = moscow_time();
+
+    let mut lines = vec![format!(
+        "*Итоги дня {} \\({}\\)*",
+        escape(&now.date_string()),
+        now.time_string()
+    )];
+
+    for (name, server_config) in servers {
+        let server = deps
+            .pool
+            .get(&server_config, || deps.config.connect(&server_config))
+            .await;
+
+        let shifts =
+            Server::list_shifts_with_offset(&mut *server.lock().await, Dates::LastNDays(1)).await;
+
+        let line = match shifts {
+            Ok(shifts) => format!(
+                "*{}*: {} \\({} смен\\)",
+                escape(&name),
+                escape(&format_ruble(Server::sum_pay_orders(&shifts))),
+                shifts.len()
+            ),
+            Err(e) => format!("*{}*: {}", escape(&name), escape(&e.to_string())),
+        };
+
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+const ALERT_COOLDOWN: Duration = Duration::from_secs(3600);
+
+fn spawn_revenue_alert(bot: Bot, deps: DependenciesForDispatcher, threshold: f64) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(deps.config.alert_check_interval_secs));
+        let mut last_alert_sent: HashMap<String, Instant> = HashMap::new();
+
+        loop {
+            tokio::select! {
+                _ = deps.background_tasks.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+
+            let Some((name, server_config)) = active_server(&deps.servers).await else {
+                continue;
+            };
+
+            if last_alert_sent
+                .get(&name)
+                .is_some_and(|sent| sent.elapsed() < ALERT_COOLDOWN)
+            {
+                continue;
+            }
+
+            let server = deps
+                .pool
+                .get(&server_config, || deps.config.connect(&server_config))
+                .await;
+
+            let shifts =
+                Server::list_shifts_with_offset(&mut *server.lock().await, Dates::LastNDays(1))
+                    .await;
+
+            let revenue = match shifts {
+                Ok(shifts) => Server::sum_pay_orders(&shifts),
+                Err(e) => {
+                    tracing::warn!(server = name, error = %e, "failed to check revenue");
+                    continue;
+                }
+            };
+
+            if revenue >= threshold {
+                continue;
+            }
+
+            let text = format!(
+                "⚠️ Выручка на сервере '{}' ниже порога: {} < {}",
+                name,
+                format_ruble(revenue),
+                format_ruble(threshold)
+            );
+
+            notify_admins(&bot, &deps, &text).await;
+
+            last_alert_sent.insert(name, Instant::now());
+        }
+    });
+}
+
+const OPEN_SHIFT_CHECK_INTERVAL: Duration = Duration::from_secs(30 * 60);
+const OPEN_SHIFT_ALERT_COOLDOWN: Duration = Duration::from_secs(3 * 3600);
+
+fn spawn_open_shift_alert(bot: Bot, deps: DependenciesForDispatcher, max_hours: u64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(OPEN_SHIFT_CHECK_INTERVAL);
+        let mut last_alert_sent: HashMap<String, Instant> = HashMap::new();
+
+        // A shift open longer than the limit started at least this many days ago,
+        // plus one because the window counts today
+        let days = max_hours.div_ceil(24) as u32 + 2;
+
+        loop {
+            tokio::select! {
+                _ = deps.background_tasks.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+
+            last_alert_sent.retain(|_, sent| sent.elapsed() < OPEN_SHIFT_ALERT_COOLDOWN);
+
+            let servers: Vec<(String, ServerConfig)> = deps
+                .servers
+                .read()
+                .await
+                .servers
+                .iter()
+                .map(|(name, server)| (name.clone(), server.clone()))
+                .collect();
+
+            for (name, server_config) in servers {
+                let server = deps
+                    .pool
+                    .get(&server_config, || deps.config.connect(&server_config))
+                    .await;
+
+                let shifts = Server::list_shifts_with_offset(
+                    &mut *server.lock().await,
+                    Dates::LastNDays(days),
+                )
+                .await;
+
+                let shifts = match shifts {
+                    Ok(shifts) => shifts,
+                    Err(e) => {
+                        tracing::warn!(server = name, error = %e, "failed to check open shifts");
+                        continue;
+                    }
+                };
+
+                for shift in Server::open_shifts(&shifts) {
+                    if last_alert_sent.contains_key(&shift.id) {
+                        continue;
+                    }
+
+                    let Some(opened) = shift.open_datetime() else {
+                        continue;
+                    };
+
+                    let hours = (moscow_now() - opened).num_hours();
+
+                    if hours < max_hours as i64 {
+                        continue;
+                    }
+
+                    let text = format!(
+                        "⚠️ Смена №{} на кассе {} (сервер '{}') открыта уже {} ч.",
+                        shift.session_number, shift.cash_reg_serial, name, hours
+                    );
+
+                    notify_admins(&bot, &deps, &text).await;
+
+                    last_alert_sent.insert(shift.id.clone(), Instant::now());
+                }
+            }
+        }
+    });
+}
+
+// Background tasks have no chat of their own, so they follow the saved
+// default server and fall back to the first one by name.
+async fn active_server(servers: &SharedServer) -> Option<(String, ServerConfig)> {
+    let registry = servers.read().await;
+
+    let name = registry.default_current();
+
+    let server = registry.servers.get(&name)?.clone();
+
+    Some((name, server))
+}
+
+async fn notify_admins(bot: &Bot, deps: &DependenciesForDispatcher, text: &str) {
+    let admins = deps.admins_list.read().await.clone();
+
+    let chat_ids: Vec<ChatId> = deps
+        .chat_registry
+        .lock()
+        .await
+        .iter()
+        .filter(|(username, _)| {
+            admins
+                .iter()
+                .any(|admin| normalize_username(admin) == normalize_username(username))
+        })
+        .map(|(_, chat_id)| *chat_id)
+        .collect();
+
+    for chat_id in chat_ids {
+        if let Err(e) = bot.send_message(chat_id, text).await {
+            tracing::warn!(chat_id = chat_id.0, error = %e, "failed to notify admin");
+        }
+    }
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!(error = %e, "failed to listen for Ctrl-C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+// Handlers write files before replying, so the only things left behind are
+// the dialogue timers and the pooled iiko sessions.
+async fn shutdown(deps: &DependenciesForDispatcher) {
+    for (_, token) in deps.timeouts.lock().await.drain() {
+        token.cancel();
+    }
+
+    deps.background_tasks.cancel();
+    deps.pool.deauth_all().await;
+
+    tracing::info!("Бот остановлен");
+}
+
+//
+
+async fn handle_states(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+    me: Me,
+) -> ResponseResult<()> {
+    let (timeout_bot, timeout_dialogue, timeout_deps) =
+        (bot.clone(), dialogue.clone(), deps.clone());
+    let chat_id = message.chat.id;
+
+    if !deps
+        .rate_limiter
+        .check(chat_id, deps.max_requests_per_minute)
+        .await
+    {
+        bot.send_message(chat_id, "Слишком много запросов, подождите немного")
+            .await?;
+        return Ok(());
+    }
+
+    // Every dialogue state is reachable only through this gate, so handlers
+    // behind it don't have to check the user again. Inline buttons bypass the
+    // dialogue and are checked in handle_callback_query.
+    let Some(username) = authorized_username(&message, &deps).await else {
+        bot.send_message(chat_id, "Вы не в списке пользователей")
+            .await?;
+
+        if let Err(e) = dialogue.update(State::None).await {
+            tracing::error!(error = %e, "failed to reset dialogue of unauthorized user");
+        }
+
+        return Ok(());
+    };
+
+    register_chat(&deps.chat_registry, username, chat_id).await;
+
+    if let Some(text) = message.text()
+        && let Ok(command) = Command::parse(text, me.username())
+    {
+        if let Err(e) = handle_command(bot, message, dialogue, command, deps).await {
+            handle_error(&timeout_bot, chat_id, e).await;
+        }
+    } else if let Some(state) = dialogue.get().await.unwrap_or_default() {
+        let result = match state.clone() {
+            State::None => handle_start(bot, message, dialogue).await,
+
+            State::Dialogue | State::OlapDishPage { .. } => {
+                callback_start(
+                    bot,
+                    message,
+                    dialogue,
+                    deps.servers,
+                    deps.allowed_list,
+                    deps.admins_list,
+                )
+                .await
+            }
+
+            State::Report => callback_reports(bot, message, dialogue, deps.clone()).await,
+
+            State::CustomDateStart => handle_custom_date_start(bot, message, dialogue).await,
+
+            State::DaysInput => callback_days_input(bot, message, dialogue, deps.clone()).await,
+
+            State::LatestShiftsCount => {
+                callback_latest_shifts(bot, message, dialogue, deps.clone()).await
+            }
+
+            State::CustomDateEnd { from } => {
+                handle_custom_date_end(bot, message, dialogue, from, deps.clone()).await
+            }
+
+            State::OlapReportType => callback_olap_report_type(bot, message, dialogue).await,
+
+            State::OlapPreset => callback_olap_preset(bot, message, dialogue, deps.clone()).await,
+
+            State::OlapPeriod { report_type } => {
+                callback_olap_period(bot, message, dialogue, deps.clone(), report_type).await
+            }
+
+            State::OlapPage(page) => {
+                callback_olap(bot, message, dialogue, deps.clone(), page).await
+            }
+
+            State::Switch => {
+                callback_switch(bot, message, deps.servers, deps.config, dialogue).await
+            }
+
+            State::Admin => {
+                callback_admin(bot, message, dialogue, deps.allowed_list, deps.admins_list).await
+            }
+
+            State::AddUser => {
+                callback_add_user(bot, message, deps.allowed_list, dialogue, deps.admins_list).await
+            }
+            State::DeleteUser => {
+                callback_delete_user(bot, message, dialogue, deps.allowed_list, deps.admins_list)
+                    .await
+            }
+
+            State::DeleteUserConfirm { username } => {
+                callback_delete_user_confirm(
+                    bot,
+                    message,
+                    dialogue,
+                    deps.allowed_list,
+                    deps.admins_list,
+                    username,
+                )
+                .await
+            }
+
+            State::AddServerName => {
+                callback_add_server_name(bot, message, dialogue, deps.clone()).await
+            }
+
+            State::AddServerUrl { name } => {
+                callback_add_server_url(bot, message, dialogue, deps.clone(), name).await
+            }
+
+            State::RemoveServerSelect => {
+                callback_remove_server(bot, message, dialogue, deps.clone()).await
+            }
+
+            State::Broadcast => callback_broadcast(bot, message, dialogue, deps.clone()).await,
+        };
+
+        if let Err(e) = result {
+            tracing::error!(error = %e, state = ?state, "handler error");
+            report_error(&timeout_bot, chat_id, e).await;
+        }
+    };
+
+    rearm_dialogue_timeout(timeout_bot, timeout_dialogue, timeout_deps).await;
+
+    Ok(())
+}
+
+async fn load_chat_registry() -> HashMap<String, ChatId> {
+    if !Path::new(CHAT_REGISTRY_PATH).exists() {
+        return HashMap::new();
+    }
+
+    read_to_struct(CHAT_REGISTRY_PATH)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "failed to read chat registry");
+            HashMap::new()
+        })
+}
+
+async fn register_chat(registry: &SharedChatRegistry, username: String, chat_id: ChatId) {
+    let mut registry = registry.lock().await;
+
+    if registry.get(&username) == Some(&chat_id) {
+        return;
+    }
+
+    registry.insert(username, chat_id);
+
+    if let Err(e) = write_struct(CHAT_REGISTRY_PATH, &*registry).await {
+        tracing::error!(error = %e, "failed to save chat registry");
+    }
+}
+
+async fn handle_error(bot: &Bot, chat_id: ChatId, error: BotError) {
+    tracing::error!(error = %error, "handler error");
+
+    report_error(bot, chat_id, error).await;
+}
+
+async fn report_error(bot: &Bot, chat_id: ChatId, error: BotError) {
+    let text = match &error {
+        BotError::IikoApi(_) => "Сервер iiko вернул ошибку, попробуйте позже".to_string(),
+        BotError::Telegram(_) => return,
+        BotError::Config(_) => "Ошибка конфигурации бота, обратитесь к администратору".to_string(),
+        BotError::Auth(_) => "Не удалось авторизоваться на сервере iiko".to_string(),
+        BotError::NotFound(e) => e.clone(),
+        BotError::Unauthorized => "Вы не в списке пользователей".to_string(),
+        BotError::Timeout => "Сервер iiko не ответил вовремя, попробуйте позже".to_string(),
+        BotError::InvalidDateRange => error.to_string(),
+    };
+
+    if let Err(e) = bot.send_message(chat_id, text).await {
+        tracing::error!(error = %e, "failed to report handler error to the user");
+    }
+}
+
+// Restarts the inactivity timer after every handled message. The main menu
+// and the idle state don't wait for input, so they have no timer.
+async fn rearm_dialogue_timeout(bot: Bot, dialogue: MyDialogue, deps: DependenciesForDispatcher) {
+    let chat_id = dialogue.chat_id();
+    let state = dialogue.get().await.ok().flatten().unwrap_or_default();
+    let waits_for_input = !matches!(
+        state,
+        State::None | State::Dialogue | State::OlapDishPage { .. }
+    );
+
+    let token = CancellationToken::new();
+
+    let previous = {
+        let mut timeouts = deps.timeouts.lock().await;
+        if waits_for_input {
+            timeouts.insert(chat_id, token.clone())
+        } else {
+            timeouts.remove(&chat_id)
+        }
+    };
+
+    if let Some(previous) = previous {
+        previous.cancel();
+    }
+
+    if !waits_for_input {
+        return;
+    }
+
+    let timeout = deps.dialogue_timeout;
+
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = token.cancelled() => {}
+            _ = tokio::time::sleep(timeout) => {
+                if dialogue.get().await.ok().flatten() != Some(state) {
+                    return;
+                }
+
+                if let Err(e) = dialogue.update(State::None).await {
+                    tracing::error!(error = %e, "failed to reset timed out dialogue");
+                    return;
+                }
+
+                if let Err(e) = bot.send_message(chat_id, "Время ожидания истекло").await {
+                    tracing::error!(error = %e, "failed to send dialogue timeout message");
+                }
+            }
+        }
+    });
+}
+
+async fn handle_command(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    command: Command,
+    deps: DependenciesForDispatcher,
+) -> Result<(), BotError> {
+    match command {
+        Command::Start => handle_start(bot, message, dialogue).await,
+
+        Command::Ping => handle_ping(bot, message, deps.servers).await,
+
+        Command::Reload => handle_reload(bot, message, deps).await,
+
+        Command::Addserver(arguments) => {
+            handle_add_server(bot, message, dialogue, deps, arguments).await
+        }
+
+        Command::Removeserver => handle_remove_server(bot, message, dialogue, deps).await,
+
+        Command::Broadcast => handle_broadcast(bot, message, dialogue, deps).await,
+
+        Command::Status => handle_status(bot, message, deps.servers, deps.config, deps.pool).await,
+
+        Command::Exportjson => {
+            handle_export_json(bot, message, deps.servers, deps.config, deps.pool).await
+        }
+
+        Command::Exportcsv(argument) => {
+            let days = if argument.trim().is_empty() {
+                7
+            } else {
+                match parse_days(&argument) {
+                    Ok(days) => days,
+                    Err(e) => {
+                        bot.send_message(message.chat.id, e).await?;
+                        return Ok(());
+                    }
+                }
+            };
+
+            handle_export_csv(bot, message, deps.servers, deps.config, deps.pool, days).await
+        }
+
+        Command::Cancel => {
+            dialogue.update(State::None).await?;
+
+            bot.send_message(message.chat.id, "Действие отменено")
+                .await?;
+
+            handle_start(bot, message, dialogue).await
+        }
+
+        Command::Lastn(argument) => {
+            if argument.trim().is_empty() {
+                return handle_days_input(bot, message, dialogue).await;
+            }
+
+            match parse_days(&argument) {
+                Ok(days) => {
+                    handle_last_n_days(
+                        bot.clone(),
+                        message.clone(),
+                        deps.servers,
+                        deps.config,
+                        deps.pool,
+                        days,
+                    )
+                    .await?;
+                    handle_start(bot, message, dialogue).await
+                }
+                Err(e) => {
+                    bot.send_message(message.chat.id, e).await?;
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+// Only reachable through the gate in handle_states, which has already
+// checked the user
+async fn handle_start(bot: Bot, message: Message, dialogue: MyDialogue) -> Result<(), BotError> {
+    let commands: Vec<BotCommand> = Command::bot_commands();
+
+    bot.set_my_commands(commands).await?;
+
+    bot.set_chat_menu_button()
+        .chat_id(message.chat.id)
+        .menu_button(teloxide::types::MenuButton::Commands)
+        .send()
+        .await?;
+
+    bot.send_message(message.chat.id, "Выберите опцию")
+        .reply_markup(build_main_keyboard())
+        .await?;
+
+    dialogue.update(State::Dialogue).await?;
+
+    Ok(())
+}
+
+//
+
+async fn callback_start(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    servers: SharedServer,
+    allowed_list: PersistentUserList,
+    admins_list: SharedAdmins,
+) -> Result<(), BotError> {
+    if let Some(text) = message.text() {
+        let result = match text {
+            "Отчёты" => handle_reports(bot, message, dialogue).await,
+            "Сменить сервер" => handle_switch(bot, message, servers, dialogue).await,
+            "Администрирование" => {
+                handle_admin(bot, message, dialogue, allowed_list, admins_list).await
+            }
+            _ => handle_start(bot, message, dialogue).await,
+        };
+
+        match result {
+            Ok(_) => {}
+            Err(e) => tracing::error!(error = %e, "handler error"),
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_callback_query(
+    bot: Bot,
+    query: CallbackQuery,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+) -> ResponseResult<()> {
+    bot.answer_callback_query(query.id.clone()).await?;
+
+    let chat_id = dialogue.chat_id();
+
+    let authorized = match &query.from.username {
+        Some(username) => is_authorized(username, &deps).await,
+        None => false,
+    };
+
+    if !authorized {
+        bot.send_message(chat_id, "Вы не в списке пользователей")
+            .await?;
+        return Ok(());
+    }
+
+    if let Err(e) = callback_olap_dish_page(&bot, &query, dialogue, deps).await {
+        handle_error(&bot, chat_id, e).await;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(names: &[&str]) -> ServerRegistry {
+        let servers = names
+            .iter()
+            .map(|name| {
+                (
+                    name.to_string(),
+                    ServerConfig::from(format!("{}.iiko.it", name)),
+                )
+            })
+            .collect();
+
+        ServerRegistry::new(servers, None).unwrap()
+    }
+
+    #[test]
+    fn chat_server_finds_a_known_server() {
+        let registry = registry(&["main", "second"]);
+
+        let server = registry.chat_server(ChatId(1), "second").unwrap();
+
+        assert_eq!(server.url, "second.iiko.it");
+    }
+
+    #[test]
+    fn chat_server_of_an_unknown_name_is_none() {
+        let registry = registry(&["main"]);
+
+        assert!(registry.chat_server(ChatId(1), "typo").is_none());
+    }
+
+    #[test]
+    fn chat_server_removed_for_the_chat_is_none() {
+        let mut registry = registry(&["main", "second"]);
+
+        chat_server_state(&mut registry, ChatId(1))
+            .map
+            .remove("second");
+
+        assert!(registry.chat_server(ChatId(1), "second").is_none());
+        assert!(registry.chat_server(ChatId(2), "second").is_some());
+    }
+
+    #[test]
+    fn validate_catches_duplicates_that_differ_by_the_at_sign() {
+        let config = TgCfg {
+            token: "token".into(),
+            accounts: vec!["@bob".into(), "bob".into()],
+            admins: vec!["admin".into()],
+            dialogue_timeout_secs: default_dialogue_timeout_secs(),
+            max_requests_per_minute: default_max_requests_per_minute(),
+            daily_report_chat_ids: Vec::new(),
+        };
+
+        assert_eq!(
+            config.validate(),
+            Err(vec![
+                "tg_cfg.toml: пользователь bob указан дважды".to_string()
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn collect_server_info_falls_back_when_the_chat_server_is_gone() {
+        let mut registry = registry(&["main", "second"]);
+
+        let state = chat_server_state(&mut registry, ChatId(1));
+        state.current = "second".into();
+        state.map.remove("second");
+
+        let servers = Arc::new(RwLock::new(registry));
+
+        let (server_config, name) = collect_server_info(servers, ChatId(1)).await.unwrap();
+
+        assert_eq!(name, "main");
+        assert_eq!(server_config.url, "main.iiko.it");
+    }
+}
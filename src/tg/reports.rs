@@ -0,0 +1,1565 @@
+use crate::cache::ReportCache;
+use crate::date::{
+    format_day, format_duration, moscow_current_quarter, moscow_now, moscow_time, parse_date_input,
+};
+use crate::error::BotError;
+use crate::export::shifts_to_csv;
+use crate::format::{clamp_message, format_ruble, format_with_dots, split_message};
+use crate::iiko::{Dates, GetShifts, Olap, Server, Shifts, compare_shifts, display_shifts_table};
+use crate::olap::{
+    CategoryDelta, Filter, OLAP_NO_DATA, OlapMap, PeriodType, ReportConfig, ReportConfigBuilder,
+    ReportType, SortOrder, compare_olap_maps, olap_page_count,
+};
+use crate::server_pool::ServerPool;
+
+//
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::vec;
+
+//
+
+use teloxide::Bot;
+use teloxide::payloads::{EditMessageTextSetters, SendMessageSetters};
+use teloxide::prelude::Requester;
+use teloxide::types::{CallbackQuery, ChatId, InputFile};
+use teloxide::{
+    types::{Message, ParseMode},
+    utils::markdown::escape,
+};
+
+//
+
+use super::keyboard::{
+    build_latest_shifts_keyboard, build_olap_period_keyboard, build_olap_presets_keyboard,
+    build_olap_report_type_keyboard, build_reports_keyboard, olap_category_keyboard,
+    olap_dish_page_keyboard,
+};
+use super::server::truncate_chars;
+use super::{
+    CachedOlap, Cfg, DependenciesForDispatcher, MyDialogue, OLAP_DISH_NEXT_PAGE,
+    OLAP_DISH_PREV_PAGE, OLAP_DISH_SORT, OLAP_DISHES_PER_PAGE, OLAP_NEXT_PAGE, OLAP_PERIODS,
+    OLAP_PRESETS, OLAP_PREV_PAGE, OLAP_REPORT_TYPES, SharedServer, State, TELEGRAM_MAX_MESSAGE_LEN,
+    collect_server_info, handle_start, message_text,
+};
+
+//
+
+pub(super) async fn handle_reports(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+) -> Result<(), BotError> {
+    bot.send_message(message.chat.id, "Выберите опцию")
+        .reply_markup(build_reports_keyboard())
+        .await?;
+
+    dialogue.update(State::Report).await?;
+
+    Ok(())
+}
+
+pub(super) async fn callback_reports(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+) -> Result<(), BotError> {
+    let (bot_cloned, message_cloned, dialogue_cloned) =
+        (bot.clone(), message.clone(), dialogue.clone());
+
+    if let Some(text) = message.text() {
+        match text {
+            "За сегодня" => {
+                handle_today(
+                    bot,
+                    message,
+                    deps.servers,
+                    deps.config,
+                    deps.pool,
+                    deps.report_cache,
+                )
+                .await?;
+                handle_start(bot_cloned, message_cloned, dialogue_cloned).await?;
+            }
+
+            "За вчера" => {
+                handle_yesterday(
+                    bot,
+                    message,
+                    deps.servers,
+                    deps.config,
+                    deps.pool,
+                    deps.report_cache,
+                )
+                .await?;
+                handle_start(bot_cloned, message_cloned, dialogue_cloned).await?;
+            }
+            "Сравнение дней" => {
+                handle_compare_days(bot, message, deps.servers, deps.config, deps.pool).await?;
+                handle_start(bot_cloned, message_cloned, dialogue_cloned).await?;
+            }
+            "За 7 дней" => {
+                handle_week(
+                    bot,
+                    message,
+                    deps.servers,
+                    deps.config,
+                    deps.pool,
+                    deps.report_cache,
+                )
+                .await?;
+                handle_start(bot_cloned, message_cloned, dialogue_cloned).await?;
+            }
+
+            "За текущий месяц" => {
+                handle_month(
+                    bot,
+                    message,
+                    deps.servers,
+                    deps.config,
+                    deps.pool,
+                    deps.report_cache,
+                )
+                .await?;
+                handle_start(bot_cloned, message_cloned, dialogue_cloned).await?;
+            }
+            "За прошлый месяц" => {
+                handle_last_month(
+                    bot,
+                    message,
+                    deps.servers,
+                    deps.config,
+                    deps.pool,
+                    deps.report_cache,
+                )
+                .await?;
+                handle_start(bot_cloned, message_cloned, dialogue_cloned).await?;
+            }
+
+            "За текущий квартал" => {
+                handle_quarter(
+                    bot,
+                    message,
+                    deps.servers,
+                    deps.config,
+                    deps.pool,
+                    deps.report_cache,
+                )
+                .await?;
+                handle_start(bot_cloned, message_cloned, dialogue_cloned).await?;
+            }
+
+            "Произвольный период" => {
+                handle_custom_period(bot, message, dialogue).await?
+            }
+
+            "Произвольный период (N дней)" => {
+                handle_days_input(bot, message, dialogue).await?
+            }
+
+            "Сравнение месяцев" => {
+                handle_compare_months(bot, message, deps.servers, deps.config, deps.pool).await?;
+                handle_start(bot_cloned, message_cloned, dialogue_cloned).await?;
+            }
+            "Последние смены" => handle_latest_shifts(bot, message, dialogue).await?,
+
+            "Olap отчёт" => handle_olap_report_type(bot, message, dialogue).await?,
+
+            "Назад" => handle_start(bot, message, dialogue).await?,
+            _ => {}
+        };
+    }
+
+    Ok(())
+}
+
+//
+
+async fn send_cached_report(
+    bot: &Bot,
+    chat_id: ChatId,
+    cache: &ReportCache,
+    key: &(String, String, String),
+    config: &Cfg,
+) -> Result<bool, BotError> {
+    let ttl = Duration::from_secs(config.report_cache_ttl_secs);
+
+    let Some(text) = cache.get(key, ttl).await else {
+        return Ok(false);
+    };
+
+    bot.send_message(chat_id, format!("{}\n\\(кэш\\)", text))
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(true)
+}
+
+async fn handle_today(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    pool: ServerPool,
+    cache: ReportCache,
+) -> Result<(), BotError> {
+    let (server_config, current_server) = collect_server_info(servers, message.chat.id).await?;
+
+    let cache_key = (
+        current_server.clone(),
+        "today".to_string(),
+        moscow_time().date_string(),
+    );
+
+    if send_cached_report(&bot, message.chat.id, &cache, &cache_key, &config).await? {
+        return Ok(());
+    }
+
+    let server = pool
+        .get(&server_config, || config.connect(&server_config))
+        .await;
+    let mut server = server.lock().await;
+
+    let shifts = Server::list_shifts_with_offset(&mut server, Dates::Week).await?;
+
+    let shift = match Server::current_shift(shifts) {
+        Ok(shift) => shift,
+        Err(BotError::NotFound(_)) => {
+            bot.send_message(message.chat.id, "Сегодня смен не найдено")
+                .await?;
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+
+    let duration = shift
+        .duration_secs()
+        .map(format_duration)
+        .unwrap_or_else(|| "—".into());
+
+    let mut date = shift.open_date;
+
+    date.truncate(10);
+
+    let text = format!(
+        "*Сервер*: *{}*\n\
+                 *Текущая смена*:\n\
+                 Дата: *{}*\n\
+                 Номер смены: *{}*\n\
+                 Статус: {} *{}*\n\
+                 Длительность: *{}*\n\
+                 Оплачено картой: *{}*\n\
+                 Оплачено наличкой: *{}*\n\
+                 Итог: *{}*",
+        escape(&current_server),
+        escape(&date),
+        escape(&format_with_dots(shift.session_number)),
+        shift.session_status.to_emoji(),
+        escape(&shift.session_status.to_string()),
+        escape(&duration),
+        escape(&format_ruble(shift.sales_card)),
+        escape(&format_ruble(shift.sales_cash as f64)),
+        escape(&format_ruble(shift.pay_orders)),
+    );
+
+    cache.insert(cache_key, text.clone()).await;
+
+    bot.send_message(message.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_yesterday(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    pool: ServerPool,
+    cache: ReportCache,
+) -> Result<(), BotError> {
+    let (server_config, current_server) = collect_server_info(servers, message.chat.id).await?;
+
+    let cache_key = (
+        current_server.clone(),
+        "yesterday".to_string(),
+        moscow_time().date_string(),
+    );
+
+    if send_cached_report(&bot, message.chat.id, &cache, &cache_key, &config).await? {
+        return Ok(());
+    }
+
+    let server = pool
+        .get(&server_config, || config.connect(&server_config))
+        .await;
+    let mut server = server.lock().await;
+
+    let shifts = Server::list_shifts_with_offset(&mut server, Dates::Week).await?;
+
+    let shift = match Server::nth_last_shift(shifts, 1) {
+        Ok(shift) => shift,
+        Err(BotError::NotFound(_)) => {
+            bot.send_message(message.chat.id, "Вчерашних данных нет")
+                .await?;
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+
+    let opened = shift.open_datetime();
+
+    let mut header = match opened {
+        Some(opened) => format!("Предыдущая смена ({})", format_day(opened.date())),
+        None => "Предыдущая смена".to_string(),
+    };
+
+    if opened.is_some_and(|opened| (moscow_now() - opened).num_hours() > 48) {
+        header.push_str(" ⚠️ (давно)");
+    }
+
+    let mut date = shift.open_date;
+
+    date.truncate(10);
+
+    let text = format!(
+        "*Сервер*: *{}*\n\
+                 *{}*:\n\
+                 Дата: *{}*\n\
+                 Номер смены: *{}*\n\
+                 Статус: {} *{}*\n\
+                 Оплачено картой: *{}*\n\
+                 Оплачено наличкой: *{}*\n\
+                 Итог: *{}*",
+        escape(&current_server),
+        escape(&header),
+        escape(&date),
+        escape(&format_with_dots(shift.session_number)),
+        shift.session_status.to_emoji(),
+        escape(&shift.session_status.to_string()),
+        escape(&format_ruble(shift.sales_card)),
+        escape(&format_ruble(shift.sales_cash as f64)),
+        escape(&format_ruble(shift.pay_orders)),
+    );
+
+    cache.insert(cache_key, text.clone()).await;
+
+    bot.send_message(message.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_compare_days(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    pool: ServerPool,
+) -> Result<(), BotError> {
+    let (server_config, current_server) = collect_server_info(servers, message.chat.id).await?;
+
+    let server = pool
+        .get(&server_config, || config.connect(&server_config))
+        .await;
+    let mut server = server.lock().await;
+
+    let shifts = Server::list_shifts_with_offset(&mut server, Dates::Week).await?;
+
+    let current = Server::current_shift(shifts.clone())?;
+    let previous = Server::nth_last_shift(shifts, 1)?;
+
+    let comparison = compare_shifts(current, previous);
+
+    let rows = [
+        (
+            "Номер смены",
+            format_with_dots(comparison.current.session_number),
+            format_with_dots(comparison.previous.session_number),
+        ),
+        (
+            "Наличные",
+            format_ruble(comparison.current.sales_cash as f64),
+            format_ruble(comparison.previous.sales_cash as f64),
+        ),
+        (
+            "Карта",
+            format_ruble(comparison.current.sales_card),
+            format_ruble(comparison.previous.sales_card),
+        ),
+        (
+            "Итог",
+            format_ruble(comparison.current.pay_orders),
+            format_ruble(comparison.previous.pay_orders),
+        ),
+    ];
+
+    let mut table = String::new();
+
+    table.push_str("```\n");
+    table.push_str(&format!(
+        "{:<11} | {:>16} | {:>16}\n",
+        "", "Текущая", "Предыдущая"
+    ));
+
+    for (label, current, previous) in &rows {
+        table.push_str(&format!(
+            "{:<11} | {:>16} | {:>16}\n",
+            label, current, previous
+        ));
+    }
+
+    table.push_str("```");
+
+    // format_ruble already prints the minus, only a gain needs its sign
+    let sign = if comparison.delta_total > 0.0 {
+        "+"
+    } else {
+        ""
+    };
+
+    let text = format!(
+        "*Сервер*: *{}*\n*Сравнение смен*:\n{}\nΔ: *{}*",
+        escape(&current_server),
+        table,
+        escape(&format!(
+            "{}{} ({}{:.1}%)",
+            sign,
+            format_ruble(comparison.delta_total),
+            sign,
+            comparison.pct_change
+        ))
+    );
+
+    bot.send_message(message.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_week(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    pool: ServerPool,
+    cache: ReportCache,
+) -> Result<(), BotError> {
+    let (server_config, current_server) = collect_server_info(servers, message.chat.id).await?;
+
+    let cache_key = (
+        current_server.clone(),
+        "week".to_string(),
+        moscow_time().date_string(),
+    );
+
+    if send_cached_report(&bot, message.chat.id, &cache, &cache_key, &config).await? {
+        return Ok(());
+    }
+
+    let server = pool
+        .get(&server_config, || config.connect(&server_config))
+        .await;
+    let mut server = server.lock().await;
+
+    let shifts = Server::list_shifts_with_offset(&mut server, Dates::Week).await?;
+
+    let days = Server::group_shifts_by_date(&shifts);
+
+    let summary = Server::sum_shifts_by_type(&shifts);
+
+    let rows = days
+        .iter()
+        .map(|(date, revenue, count)| {
+            (
+                date.format("%d.%m.%Y").to_string(),
+                format_ruble(*revenue),
+                count.to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let revenue_width = rows
+        .iter()
+        .map(|(_, revenue, _)| revenue.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("выручка".chars().count());
+
+    let mut table = String::new();
+
+    table.push_str("```\n");
+    table.push_str(&format!(
+        "{:<10} | {:<revenue_width$} | смен\n",
+        "дата", "выручка"
+    ));
+
+    for (date, revenue, count) in &rows {
+        table.push_str(&format!(
+            "{:<10} | {:>revenue_width$} | {}\n",
+            date, revenue, count
+        ));
+    }
+
+    table.push_str("```");
+
+    let text = format!(
+        "*Сервер*: *{}*\n*Выручка за прошедшие 7 дней*:\n{}\nНаличные: {} \\| Карта: {} \\| *Итого*: *{}*",
+        escape(&current_server),
+        table,
+        escape(&format_ruble(summary.cash as f64)),
+        escape(&format_ruble(summary.card)),
+        escape(&format_ruble(summary.total))
+    );
+
+    cache.insert(cache_key, text.clone()).await;
+
+    bot.send_message(message.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_month(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    pool: ServerPool,
+    cache: ReportCache,
+) -> Result<(), BotError> {
+    let (server_config, current_server) = collect_server_info(servers, message.chat.id).await?;
+
+    let cache_key = (
+        current_server.clone(),
+        "month".to_string(),
+        moscow_time().date_string(),
+    );
+
+    if send_cached_report(&bot, message.chat.id, &cache, &cache_key, &config).await? {
+        return Ok(());
+    }
+
+    let server = pool
+        .get(&server_config, || config.connect(&server_config))
+        .await;
+    let mut server = server.lock().await;
+
+    let shifts = Server::list_shifts_with_offset(&mut server, Dates::ThisMonth).await?;
+
+    let text = format_month_report(&current_server, &shifts, "Итого за месяц");
+
+    cache.insert(cache_key, text.clone()).await;
+
+    bot.send_message(message.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+//
+
+async fn handle_last_month(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    pool: ServerPool,
+    cache: ReportCache,
+) -> Result<(), BotError> {
+    let (server_config, current_server) = collect_server_info(servers, message.chat.id).await?;
+
+    let cache_key = (
+        current_server.clone(),
+        "last_month".to_string(),
+        moscow_time().date_string(),
+    );
+
+    if send_cached_report(&bot, message.chat.id, &cache, &cache_key, &config).await? {
+        return Ok(());
+    }
+
+    let server = pool
+        .get(&server_config, || config.connect(&server_config))
+        .await;
+    let mut server = server.lock().await;
+
+    let shifts = Server::list_shifts_with_offset(&mut server, Dates::LastMonth).await?;
+
+    let text = format_month_report(&current_server, &shifts, "Итого за прошлый месяц");
+
+    cache.insert(cache_key, text.clone()).await;
+
+    bot.send_message(message.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_quarter(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    pool: ServerPool,
+    cache: ReportCache,
+) -> Result<(), BotError> {
+    let (server_config, current_server) = collect_server_info(servers, message.chat.id).await?;
+
+    let quarter = moscow_current_quarter();
+
+    let cache_key = (
+        current_server.clone(),
+        format!("quarter_{}", quarter),
+        moscow_time().date_string(),
+    );
+
+    if send_cached_report(&bot, message.chat.id, &cache, &cache_key, &config).await? {
+        return Ok(());
+    }
+
+    let server = pool
+        .get(&server_config, || config.connect(&server_config))
+        .await;
+    let mut server = server.lock().await;
+
+    let shifts = Server::list_shifts_with_offset(&mut server, Dates::Quarter(quarter)).await?;
+
+    let text = format_month_report(
+        &current_server,
+        &shifts,
+        &format!("Итого за {} квартал", quarter),
+    );
+
+    cache.insert(cache_key, text.clone()).await;
+
+    bot.send_message(message.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+fn format_month_report(current_server: &str, shifts: &Shifts, total_label: &str) -> String {
+    let weeks = Server::sum_shifts_by_week(shifts);
+
+    let summary = Server::sum_shifts_by_type(shifts);
+
+    let spans_years = weeks
+        .first()
+        .zip(weeks.last())
+        .is_some_and(|(((first, _), _), ((last, _), _))| first != last);
+
+    let lines = weeks
+        .iter()
+        .map(|((year, week), revenue)| {
+            let label = if spans_years {
+                format!("Неделя {} ({})", week, year)
+            } else {
+                format!("Неделя {}", week)
+            };
+
+            format!("{}: {}", escape(&label), escape(&format_ruble(*revenue)))
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    format!(
+        "*Сервер*: *{}*\n{}\nНаличные: {} \\| Карта: {} \\| *{}*: *{}*",
+        escape(current_server),
+        lines,
+        escape(&format_ruble(summary.cash as f64)),
+        escape(&format_ruble(summary.card)),
+        total_label,
+        escape(&format_ruble(summary.total))
+    )
+}
+
+async fn handle_custom_period(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+) -> Result<(), BotError> {
+    bot.send_message(
+        message.chat.id,
+        "Введите начальную дату (например, 2024-01-01)",
+    )
+    .await?;
+
+    dialogue.update(State::CustomDateStart).await?;
+
+    Ok(())
+}
+
+pub(super) async fn handle_custom_date_start(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+) -> Result<(), BotError> {
+    let text = message_text(&message)?;
+
+    let from = match parse_date_input(text) {
+        Ok(date) => date,
+        Err(e) => {
+            bot.send_message(message.chat.id, e).await?;
+            return Ok(());
+        }
+    };
+
+    bot.send_message(message.chat.id, "Введите конечную дату")
+        .await?;
+
+    dialogue.update(State::CustomDateEnd { from }).await?;
+
+    Ok(())
+}
+
+pub(super) async fn handle_custom_date_end(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    from: String,
+    deps: DependenciesForDispatcher,
+) -> Result<(), BotError> {
+    let text = message_text(&message)?;
+
+    let to = match parse_date_input(text) {
+        Ok(date) => date,
+        Err(e) => {
+            bot.send_message(message.chat.id, e).await?;
+            return Ok(());
+        }
+    };
+
+    if from > to {
+        bot.send_message(
+            message.chat.id,
+            "Конечная дата раньше начальной, введите конечную дату ещё раз",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    handle_custom_report(
+        bot.clone(),
+        message.clone(),
+        deps.servers,
+        deps.config,
+        deps.pool,
+        from,
+        to,
+    )
+    .await?;
+
+    handle_start(bot, message, dialogue).await?;
+
+    Ok(())
+}
+
+async fn handle_custom_report(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    pool: ServerPool,
+    from: String,
+    to: String,
+) -> Result<(), BotError> {
+    let (server_config, current_server) = collect_server_info(servers, message.chat.id).await?;
+
+    let server = pool
+        .get(&server_config, || config.connect(&server_config))
+        .await;
+    let mut server = server.lock().await;
+
+    let dates = Dates::Custom {
+        from: from.clone(),
+        to: to.clone(),
+    };
+
+    let shifts = Server::list_shifts_with_offset(&mut server, dates).await?;
+
+    let sum = Server::sum_pay_orders(&shifts);
+
+    let text = format!(
+        "*Сервер*: *{}*\n*Сумма за период с {} по {}*: *{}*",
+        escape(&current_server),
+        escape(&from),
+        escape(&to),
+        escape(&format_ruble(sum))
+    );
+
+    bot.send_message(message.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+//
+
+const LATEST_SHIFTS_COUNTS: [usize; 3] = [3, 5, 10];
+
+async fn handle_latest_shifts(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+) -> Result<(), BotError> {
+    bot.send_message(message.chat.id, "Сколько последних смен показать?")
+        .reply_markup(build_latest_shifts_keyboard(&LATEST_SHIFTS_COUNTS))
+        .await?;
+
+    dialogue.update(State::LatestShiftsCount).await?;
+
+    Ok(())
+}
+
+pub(super) async fn callback_latest_shifts(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+) -> Result<(), BotError> {
+    let text = message_text(&message)?;
+
+    if text == "Назад" {
+        return handle_reports(bot, message, dialogue).await;
+    }
+
+    let Some(count) = text
+        .parse::<usize>()
+        .ok()
+        .filter(|count| LATEST_SHIFTS_COUNTS.contains(count))
+    else {
+        bot.send_message(message.chat.id, "Выберите количество на клавиатуре")
+            .await?;
+        return Ok(());
+    };
+
+    let (server_config, current_server) =
+        collect_server_info(deps.servers, message.chat.id).await?;
+
+    let server = deps
+        .pool
+        .get(&server_config, || deps.config.connect(&server_config))
+        .await;
+
+    let shifts =
+        Server::list_shifts_with_offset(&mut *server.lock().await, Dates::LastNDays(30)).await?;
+
+    let shifts = Server::latest_n_shifts(shifts, count);
+
+    let text = format!(
+        "*Сервер*: *{}*\n*Последние смены*:\n{}",
+        escape(&current_server),
+        display_shifts_table(&shifts)
+    );
+
+    bot.send_message(message.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    handle_start(bot, message, dialogue).await?;
+
+    Ok(())
+}
+
+//
+
+pub(super) fn parse_days(text: &str) -> Result<u32, String> {
+    match text.trim().parse::<u32>() {
+        Ok(days) if (1..=365).contains(&days) => Ok(days),
+        _ => Err("Введите число дней от 1 до 365".into()),
+    }
+}
+
+pub(super) async fn handle_days_input(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+) -> Result<(), BotError> {
+    bot.send_message(message.chat.id, "Введите количество дней (от 1 до 365)")
+        .await?;
+
+    dialogue.update(State::DaysInput).await?;
+
+    Ok(())
+}
+
+pub(super) async fn callback_days_input(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+) -> Result<(), BotError> {
+    let text = message_text(&message)?;
+
+    let days = match parse_days(text) {
+        Ok(days) => days,
+        Err(e) => {
+            bot.send_message(message.chat.id, e).await?;
+            return Ok(());
+        }
+    };
+
+    handle_last_n_days(
+        bot.clone(),
+        message.clone(),
+        deps.servers,
+        deps.config,
+        deps.pool,
+        days,
+    )
+    .await?;
+
+    handle_start(bot, message, dialogue).await?;
+
+    Ok(())
+}
+
+pub(super) async fn handle_last_n_days(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    pool: ServerPool,
+    days: u32,
+) -> Result<(), BotError> {
+    let (server_config, current_server) = collect_server_info(servers, message.chat.id).await?;
+
+    let server = pool
+        .get(&server_config, || config.connect(&server_config))
+        .await;
+    let mut server = server.lock().await;
+
+    let shifts = Server::list_shifts_with_offset(&mut server, Dates::LastNDays(days)).await?;
+
+    let sum = Server::sum_pay_orders(&shifts);
+
+    let text = format!(
+        "*Сервер*: *{}*\n*Сумма за последние {} дней*: *{}*",
+        escape(&current_server),
+        days,
+        escape(&format_ruble(sum))
+    );
+
+    bot.send_message(message.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+//
+
+pub(super) async fn handle_export_json(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    pool: ServerPool,
+) -> Result<(), BotError> {
+    let (server_config, _) = collect_server_info(servers, message.chat.id).await?;
+
+    let server = pool
+        .get(&server_config, || config.connect(&server_config))
+        .await;
+    let mut server = server.lock().await;
+
+    let shifts = Server::list_shifts_with_offset(&mut server, Dates::LastNDays(30)).await?;
+
+    let json = serde_json::to_string_pretty(&shifts)?;
+
+    let document = InputFile::memory(json.into_bytes())
+        .file_name(format!("shifts_{}.json", moscow_time().date_string()));
+
+    bot.send_document(message.chat.id, document).await?;
+
+    Ok(())
+}
+
+pub(super) async fn handle_export_csv(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    pool: ServerPool,
+    days: u32,
+) -> Result<(), BotError> {
+    let (server_config, _) = collect_server_info(servers, message.chat.id).await?;
+
+    let server = pool
+        .get(&server_config, || config.connect(&server_config))
+        .await;
+    let mut server = server.lock().await;
+
+    let shifts = Server::list_shifts_with_offset(&mut server, Dates::LastNDays(days)).await?;
+
+    let csv = shifts_to_csv(&shifts);
+
+    // Telegram picks the MIME type (text/csv) from the file extension
+    let document = InputFile::memory(csv.into_bytes())
+        .file_name(format!("shifts_{}.csv", moscow_time().date_string()));
+
+    bot.send_document(message.chat.id, document).await?;
+
+    Ok(())
+}
+
+//
+
+fn olap_report_config(
+    config: &Cfg,
+    report_type: ReportType,
+    period: PeriodType,
+) -> Result<ReportConfig, BotError> {
+    let mut builder = ReportConfigBuilder::new()
+        .report_type(report_type)
+        .row_field("DishCategory")
+        .col_field("DishName")
+        .aggregate("GuestNum")
+        .aggregate("DishDiscountSumInt")
+        .filter(
+            "OpenDate.Typed",
+            Filter::DateRange {
+                periodType: period,
+                to: moscow_time().date_string(),
+            },
+        )
+        .filter(
+            "DeletedWithWriteoff",
+            Filter::IncludeValues {
+                values: vec!["NOT_DELETED".into()],
+            },
+        )
+        .exclude_deleted(config.olap_exclude_deleted);
+
+    if let Some(min_revenue) = config.olap_min_revenue {
+        builder = builder.filter(
+            "DishDiscountSumInt",
+            Filter::NumberRange {
+                from: min_revenue,
+                to: 999_999_999.0,
+            },
+        );
+    }
+
+    builder.build().map_err(BotError::Config)
+}
+
+async fn fetch_olap(
+    servers: SharedServer,
+    pool: &ServerPool,
+    config: &Cfg,
+    chat_id: ChatId,
+    form: &ReportConfig,
+) -> Result<(OlapMap, String), BotError> {
+    let (server_config, current_server) = collect_server_info(servers, chat_id).await?;
+    let server = pool
+        .get(&server_config, || config.connect(&server_config))
+        .await;
+
+    let form_json = serde_json::to_string_pretty(form)?;
+
+    let olap = Server::get_olap(form_json, &mut *server.lock().await).await?;
+
+    Ok((olap, current_server))
+}
+
+async fn handle_olap_report_type(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+) -> Result<(), BotError> {
+    bot.send_message(message.chat.id, "Тип отчёта")
+        .reply_markup(build_olap_report_type_keyboard())
+        .await?;
+
+    dialogue.update(State::OlapReportType).await?;
+
+    Ok(())
+}
+
+pub(super) async fn callback_olap_report_type(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+) -> Result<(), BotError> {
+    let text = message_text(&message)?;
+
+    if text == "Назад" {
+        return handle_reports(bot, message, dialogue).await;
+    }
+
+    if text == "Быстрые отчёты" {
+        return handle_olap_presets(bot, message, dialogue).await;
+    }
+
+    let Some(report_type) = OLAP_REPORT_TYPES
+        .iter()
+        .find(|report_type| report_type.description() == text)
+    else {
+        return handle_olap_report_type(bot, message, dialogue).await;
+    };
+
+    handle_olap_period(bot, message, dialogue, *report_type).await
+}
+
+async fn handle_olap_presets(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+) -> Result<(), BotError> {
+    bot.send_message(message.chat.id, "Быстрые отчёты")
+        .reply_markup(build_olap_presets_keyboard())
+        .await?;
+
+    dialogue.update(State::OlapPreset).await?;
+
+    Ok(())
+}
+
+pub(super) async fn callback_olap_preset(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+) -> Result<(), BotError> {
+    let text = message_text(&message)?;
+
+    if text == "Назад" {
+        return handle_olap_report_type(bot, message, dialogue).await;
+    }
+
+    let Some((_, preset)) = OLAP_PRESETS.iter().find(|(label, _)| *label == text) else {
+        return handle_olap_presets(bot, message, dialogue).await;
+    };
+
+    let config = preset(deps.config.olap_exclude_deleted);
+
+    handle_olap(bot, message, dialogue, deps, config).await
+}
+
+async fn handle_olap_period(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    report_type: ReportType,
+) -> Result<(), BotError> {
+    bot.send_message(message.chat.id, "Выберите период")
+        .reply_markup(build_olap_period_keyboard())
+        .await?;
+
+    dialogue.update(State::OlapPeriod { report_type }).await?;
+
+    Ok(())
+}
+
+pub(super) async fn callback_olap_period(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+    report_type: ReportType,
+) -> Result<(), BotError> {
+    let text = message_text(&message)?;
+
+    if text == "Назад" {
+        return handle_olap_report_type(bot, message, dialogue).await;
+    }
+
+    let Some((_, period)) = OLAP_PERIODS.iter().find(|(label, _)| *label == text) else {
+        return handle_olap_period(bot, message, dialogue, report_type).await;
+    };
+
+    let form = olap_report_config(&deps.config, report_type, *period)?;
+
+    handle_olap(bot, message, dialogue, deps, form).await
+}
+
+async fn handle_olap(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+    form: ReportConfig,
+) -> Result<(), BotError> {
+    let (olap, current_server) = fetch_olap(
+        deps.servers,
+        &deps.pool,
+        &deps.config,
+        message.chat.id,
+        &form,
+    )
+    .await?;
+
+    deps.olap_store.lock().await.insert(
+        message.chat.id,
+        CachedOlap {
+            olap: olap.clone(),
+            fetched: Instant::now(),
+            form,
+        },
+    );
+
+    if olap.is_empty() {
+        bot.send_message(message.chat.id, "По вашим фильтрам ничего не найдено.")
+            .await?;
+        return Ok(());
+    }
+
+    let (keyboard, pages) = olap_category_keyboard(&olap, 0);
+
+    let text = format!(
+        "Режим Olap отчёта\\. Текущий сервер: *{}*\nКатегории \\(стр\\. 1/{}\\)",
+        escape(&current_server),
+        pages
+    );
+
+    bot.send_message(message.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .reply_markup(keyboard)
+        .await?;
+
+    dialogue.update(State::OlapPage(0)).await?;
+
+    Ok(())
+}
+
+// A percentage is meaningless without last month's revenue: the category is
+// either new or had no sales in both months
+fn format_delta_pct(delta: &CategoryDelta) -> String {
+    if delta.previous_revenue == 0.0 {
+        if delta.current_revenue == 0.0 {
+            return "—".to_string();
+        }
+        return "новая".to_string();
+    }
+
+    let sign = if delta.delta < 0.0 { "-" } else { "+" };
+    format!("{}{:.1}%", sign, delta.pct.abs())
+}
+
+async fn handle_compare_months(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    pool: ServerPool,
+) -> Result<(), BotError> {
+    let (current, current_server) = fetch_olap(
+        servers.clone(),
+        &pool,
+        &config,
+        message.chat.id,
+        &olap_report_config(&config, ReportType::SALES, PeriodType::CURRENT_MONTH)?,
+    )
+    .await?;
+
+    let (previous, _) = fetch_olap(
+        servers,
+        &pool,
+        &config,
+        message.chat.id,
+        &olap_report_config(&config, ReportType::SALES, PeriodType::PREVIOUS_MONTH)?,
+    )
+    .await?;
+
+    let mut deltas: Vec<(String, CategoryDelta)> =
+        compare_olap_maps(&current, &previous).into_iter().collect();
+
+    if deltas.is_empty() {
+        bot.send_message(message.chat.id, "По вашим фильтрам ничего не найдено.")
+            .await?;
+        return Ok(());
+    }
+
+    deltas.sort_by(|a, b| b.1.current_revenue.total_cmp(&a.1.current_revenue));
+
+    let mut table = String::new();
+
+    table.push_str("```\n");
+    table.push_str(&format!(
+        "{:<15} | {:>16} | {:>16} | {:>7}\n",
+        "Категория", "Текущий", "Прошлый", "Δ"
+    ));
+
+    for (category, delta) in &deltas {
+        table.push_str(&format!(
+            "{:<15} | {:>16} | {:>16} | {:>7}\n",
+            truncate_chars(category, 15),
+            format_ruble(delta.current_revenue),
+            format_ruble(delta.previous_revenue),
+            format_delta_pct(delta)
+        ));
+    }
+
+    table.push_str("```");
+
+    let text = format!(
+        "*Сервер*: *{}*\n*Сравнение месяцев*:\n{}",
+        escape(&current_server),
+        table
+    );
+
+    for chunk in split_message(&text, TELEGRAM_MAX_MESSAGE_LEN) {
+        bot.send_message(message.chat.id, chunk)
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+    }
+
+    Ok(())
+}
+
+//
+
+pub(super) async fn callback_olap(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+    page: usize,
+) -> Result<(), BotError> {
+    let data = message_text(&message)?;
+
+    let olap = cached_olap(&bot, message.chat.id, &deps).await?;
+
+    let new_page = match data {
+        OLAP_PREV_PAGE => Some(page.saturating_sub(1)),
+        OLAP_NEXT_PAGE => Some(page + 1),
+        _ => None,
+    };
+
+    if let Some(new_page) = new_page {
+        let (keyboard, pages) = olap_category_keyboard(&olap, new_page);
+        let new_page = new_page.min(pages - 1);
+
+        bot.send_message(
+            message.chat.id,
+            format!("Категории (стр. {}/{})", new_page + 1, pages),
+        )
+        .reply_markup(keyboard)
+        .await?;
+
+        dialogue.update(State::OlapPage(new_page)).await?;
+
+        return Ok(());
+    }
+
+    let category = data.to_string();
+    let mut shown = false;
+
+    if data != "Назад" && !olap.contains_key(data) {
+        tracing::warn!(chat_id = %message.chat.id, category = data, "OLAP category not found");
+
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "Данные по категории '{}' не найдены. Возможно, отчёт устарел — запустите снова.",
+                data
+            ),
+        )
+        .await?;
+
+        if !olap.is_empty() {
+            let (keyboard, pages) = olap_category_keyboard(&olap, page);
+            let page = page.min(pages - 1);
+
+            bot.send_message(
+                message.chat.id,
+                format!("Категории (стр. {}/{})", page + 1, pages),
+            )
+            .reply_markup(keyboard)
+            .await?;
+
+            dialogue.update(State::OlapPage(page)).await?;
+
+            return Ok(());
+        }
+    }
+
+    if let Some(olap_elements) = olap.get(data) {
+        let sort = chat_sort_order(&deps, message.chat.id).await;
+        let text = Server::display_olap_page(olap_elements, 0, OLAP_DISHES_PER_PAGE, sort);
+        let dish_pages = olap_page_count(olap_elements.len(), OLAP_DISHES_PER_PAGE);
+
+        if text == OLAP_NO_DATA {
+            bot.send_message(message.chat.id, text).await?;
+        } else {
+            // Wrapped dish names add rows, so even one page can outgrow a message
+            let mut chunks = split_message(&text, TELEGRAM_MAX_MESSAGE_LEN);
+            let last = chunks.pop().unwrap_or_default();
+
+            for chunk in chunks {
+                bot.send_message(message.chat.id, chunk)
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await?;
+            }
+
+            bot.send_message(message.chat.id, last)
+                .parse_mode(ParseMode::MarkdownV2)
+                .reply_markup(olap_dish_page_keyboard(0, dish_pages, sort))
+                .await?;
+
+            shown = true;
+        }
+    }
+
+    dialogue.update(State::None).await?;
+
+    handle_start(bot, message, dialogue.clone()).await?;
+
+    // The main menu stays usable; this state only remembers which table the
+    // inline page and sort buttons belong to.
+    if shown {
+        dialogue
+            .update(State::OlapDishPage { category, page: 0 })
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn cached_olap(
+    bot: &Bot,
+    chat_id: ChatId,
+    deps: &DependenciesForDispatcher,
+) -> Result<OlapMap, BotError> {
+    let cached = deps.olap_store.lock().await.get(&chat_id).cloned();
+    let ttl = Duration::from_secs(deps.config.olap_cache_ttl_secs);
+
+    let form = match cached {
+        Some(cached) if cached.fetched.elapsed() < ttl => return Ok(cached.olap),
+        Some(cached) => cached.form,
+        None => olap_report_config(&deps.config, ReportType::SALES, PeriodType::CURRENT_MONTH)?,
+    };
+
+    bot.send_message(chat_id, "Данные устарели, обновляем...")
+        .await?;
+
+    let (olap, _) = fetch_olap(
+        deps.servers.clone(),
+        &deps.pool,
+        &deps.config,
+        chat_id,
+        &form,
+    )
+    .await?;
+
+    deps.olap_store.lock().await.insert(
+        chat_id,
+        CachedOlap {
+            olap: olap.clone(),
+            fetched: Instant::now(),
+            form,
+        },
+    );
+
+    Ok(olap)
+}
+
+async fn chat_sort_order(deps: &DependenciesForDispatcher, chat_id: ChatId) -> SortOrder {
+    deps.sort_orders
+        .lock()
+        .await
+        .get(&chat_id)
+        .copied()
+        .unwrap_or_default()
+}
+
+//
+
+pub(super) async fn callback_olap_dish_page(
+    bot: &Bot,
+    query: &CallbackQuery,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+) -> Result<(), BotError> {
+    let Some(State::OlapDishPage { category, page }) = dialogue.get().await? else {
+        return Ok(());
+    };
+
+    let Some(message) = query.regular_message() else {
+        return Ok(());
+    };
+
+    let mut sort = chat_sort_order(&deps, message.chat.id).await;
+
+    let page = match query.data.as_deref() {
+        Some(OLAP_DISH_PREV_PAGE) => page.saturating_sub(1),
+        Some(OLAP_DISH_NEXT_PAGE) => page + 1,
+        Some(OLAP_DISH_SORT) => {
+            sort = sort.next();
+            deps.sort_orders.lock().await.insert(message.chat.id, sort);
+            0
+        }
+        _ => return Ok(()),
+    };
+
+    let olap = cached_olap(bot, message.chat.id, &deps).await?;
+
+    let Some(olap_elements) = olap.get(&category) else {
+        return Ok(());
+    };
+
+    let pages = olap_page_count(olap_elements.len(), OLAP_DISHES_PER_PAGE);
+    let page = page.min(pages - 1);
+
+    let text = Server::display_olap_page(olap_elements, page, OLAP_DISHES_PER_PAGE, sort);
+
+    // An edit can't be split into several messages
+    let text = clamp_message(&text, TELEGRAM_MAX_MESSAGE_LEN);
+
+    bot.edit_message_text(message.chat.id, message.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .reply_markup(olap_dish_page_keyboard(page, pages, sort))
+        .await?;
+
+    dialogue
+        .update(State::OlapDishPage { category, page })
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(current_revenue: f64, previous_revenue: f64) -> CategoryDelta {
+        let delta = current_revenue - previous_revenue;
+        let pct = if previous_revenue == 0.0 {
+            0.0
+        } else {
+            delta / previous_revenue * 100.0
+        };
+
+        CategoryDelta {
+            current_revenue,
+            previous_revenue,
+            delta,
+            pct,
+        }
+    }
+
+    #[test]
+    fn delta_pct_is_signed() {
+        assert_eq!(format_delta_pct(&delta(150.0, 100.0)), "+50.0%");
+        assert_eq!(format_delta_pct(&delta(75.0, 100.0)), "-25.0%");
+        assert_eq!(format_delta_pct(&delta(100.0, 100.0)), "+0.0%");
+    }
+
+    #[test]
+    fn delta_pct_without_previous_revenue() {
+        assert_eq!(format_delta_pct(&delta(500.0, 0.0)), "новая");
+        assert_eq!(format_delta_pct(&delta(0.0, 0.0)), "—");
+    }
+}
@@ -0,0 +1,587 @@
+use crate::circuit_breaker::CircuitState;
+use crate::error::BotError;
+use crate::iiko::{Dates, GetShifts, Server};
+use crate::server_pool::ServerPool;
+use crate::shared::{ServerConfig, read_to_struct, write_struct};
+
+//
+
+use std::sync::Arc;
+use std::time::Duration;
+
+//
+
+use futures::future::join_all;
+
+//
+
+use teloxide::Bot;
+use teloxide::payloads::SendMessageSetters;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::types::{Message, ParseMode};
+use teloxide::utils::markdown::escape;
+
+//
+
+use super::keyboard::build_switch_keyboard;
+use super::{
+    CANCEL, CFG_PATH, Cfg, DependenciesForDispatcher, MyDialogue, SharedServer, State,
+    chat_server_state, handle_start, is_admin, message_text, sender_username,
+};
+
+//
+
+pub(super) async fn handle_ping(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+) -> Result<(), BotError> {
+    let mut entries = servers
+        .read()
+        .await
+        .servers
+        .iter()
+        .map(|(name, server)| (name.clone(), server.clone()))
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let pings = entries
+        .iter()
+        .map(|(_, server)| Server::ping(server, Duration::from_secs(5)));
+
+    let results = join_all(pings).await;
+
+    let text = entries
+        .iter()
+        .zip(results)
+        .map(|((name, _), result)| match result {
+            Ok(latency) => format!("Сервер: {} — {}ms", name, latency.as_millis()),
+            Err(e) => format!("Сервер: {} — недоступен ({})", name, e),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    bot.send_message(message.chat.id, text).await?;
+
+    Ok(())
+}
+
+pub(super) fn truncate_chars(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+
+    let mut truncated = text.chars().take(max - 1).collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+pub(super) async fn handle_status(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    pool: ServerPool,
+) -> Result<(), BotError> {
+    let (current_server, mut entries) = {
+        let registry = servers.read().await;
+        let entries = registry
+            .chat_servers(message.chat.id)
+            .iter()
+            .map(|(name, server)| (name.clone(), server.clone()))
+            .collect::<Vec<_>>();
+        (registry.chat_current(message.chat.id), entries)
+    };
+
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let pings = entries
+        .iter()
+        .map(|(_, server)| Server::ping(server, Duration::from_secs(5)));
+
+    let results: Vec<Result<Duration, BotError>> = join_all(pings).await;
+
+    let shift_statuses = entries
+        .iter()
+        .zip(&results)
+        .map(|((_, server_config), ping)| {
+            let (pool, config) = (&pool, &config);
+
+            async move {
+                ping.as_ref().ok()?;
+
+                let server = pool
+                    .get(server_config, || config.connect(server_config))
+                    .await;
+
+                let shifts =
+                    Server::list_shifts_with_offset(&mut *server.lock().await, Dates::LastNDays(1))
+                        .await
+                        .ok()?;
+
+                Server::current_shift(shifts)
+                    .ok()
+                    .map(|shift| shift.session_status)
+            }
+        });
+
+    let shift_statuses = join_all(shift_statuses).await;
+
+    let mut table = String::new();
+
+    table.push_str("```\n");
+
+    for (((name, server), result), shift_status) in
+        entries.iter().zip(&results).zip(&shift_statuses)
+    {
+        let marker = if *name == current_server { '★' } else { ' ' };
+
+        let (emoji, status) = match result {
+            Ok(latency) => ("🟢", format!("{}ms", latency.as_millis())),
+            Err(BotError::Timeout) => ("🔴", "недоступен (таймаут)".to_string()),
+            Err(_) => ("🔴", "недоступен (ошибка)".to_string()),
+        };
+
+        // Unqueried servers have nothing to trip and busy ones are mid-request
+        let circuit = pool
+            .circuit_state(server)
+            .await
+            .unwrap_or(CircuitState::Closed);
+
+        let shift = shift_status
+            .as_ref()
+            .map_or("—", |status| status.to_emoji());
+
+        table.push_str(&format!(
+            "{} {} {} | {} | {} | смена {} | цепь {}\n",
+            marker,
+            emoji,
+            name,
+            truncate_chars(&server.to_string(), 24),
+            status,
+            shift,
+            circuit.to_emoji()
+        ));
+    }
+
+    table.push_str("```\n");
+
+    bot.send_message(message.chat.id, table)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+pub(super) async fn handle_switch(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    dialogue: MyDialogue,
+) -> Result<(), BotError> {
+    let (current_server, server_keys) = {
+        let registry = servers.read().await;
+        let keys = registry
+            .chat_servers(message.chat.id)
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        (registry.chat_current(message.chat.id), keys)
+    };
+
+    let keyboard = build_switch_keyboard(&server_keys);
+
+    let text = format!("Текущий сервер: *{}*", escape(&current_server));
+
+    bot.send_message(message.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .reply_markup(keyboard)
+        .await?;
+
+    dialogue.update(State::Switch).await?;
+
+    Ok(())
+}
+
+pub(super) async fn callback_switch(
+    bot: Bot,
+    message: Message,
+    servers: SharedServer,
+    config: Arc<Cfg>,
+    dialogue: MyDialogue,
+) -> Result<(), BotError> {
+    let data = message_text(&message)?;
+
+    // Keyboards sent before "Отмена" was added still carry "Назад"
+    if data == CANCEL || data == "Назад" {
+        dialogue.update(State::None).await?;
+        return handle_start(bot, message, dialogue).await;
+    }
+
+    let (url, previous) = {
+        let registry = servers.read().await;
+        (
+            registry.chat_server(message.chat.id, data),
+            registry.chat_current(message.chat.id),
+        )
+    };
+
+    // The name may be mistyped or the server removed since the keyboard was sent
+    let Some(url) = url else {
+        bot.send_message(message.chat.id, "Сервер не найден")
+            .await?;
+        return handle_switch(bot, message, servers, dialogue).await;
+    };
+
+    if Server::ping(&url, Duration::from_secs(3)).await.is_err() {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "Сервер '{}' недоступен, переключение отменено. Текущий сервер: '{}'",
+                data, previous
+            ),
+        )
+        .await?;
+
+        dialogue.update(State::None).await?;
+
+        return handle_start(bot, message, dialogue).await;
+    }
+
+    // Only this chat's choice changes; the default for other chats stays put
+    let mut states = servers.write().await;
+    chat_server_state(&mut states, message.chat.id).current = data.to_string();
+    let state = states.persisted();
+    drop(states);
+
+    write_struct(&config.state_file, &state).await?;
+
+    bot.send_message(
+        message.chat.id,
+        format!("Текущий сервер теперь '{}' -> {}", data, url),
+    )
+    .await?;
+
+    dialogue.update(State::None).await?;
+
+    handle_start(bot, message, dialogue).await?;
+
+    Ok(())
+}
+
+pub(super) async fn handle_add_server(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+    arguments: String,
+) -> Result<(), BotError> {
+    let username = sender_username(&message)?;
+
+    if !is_admin(deps.admins_list.clone(), &username).await {
+        bot.send_message(message.chat.id, "Вы не находитесь в списке админов")
+            .await?;
+        return Ok(());
+    }
+
+    let mut arguments = arguments.split_whitespace();
+
+    match (arguments.next(), arguments.next()) {
+        (Some(name), Some(url)) => {
+            add_server(&bot, message.chat.id, &deps, name, url).await?;
+        }
+
+        (Some(name), None) => {
+            if let Err(e) = validate_server_name(name) {
+                bot.send_message(message.chat.id, e).await?;
+                return Ok(());
+            }
+
+            bot.send_message(message.chat.id, "Введите адрес сервера")
+                .await?;
+
+            dialogue
+                .update(State::AddServerUrl {
+                    name: name.to_string(),
+                })
+                .await?;
+        }
+
+        _ => {
+            bot.send_message(message.chat.id, "Введите имя сервера")
+                .await?;
+
+            dialogue.update(State::AddServerName).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) async fn callback_add_server_name(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+) -> Result<(), BotError> {
+    let name = message_text(&message)?.trim();
+
+    if let Err(e) = validate_server_name(name) {
+        bot.send_message(message.chat.id, e).await?;
+        return Ok(());
+    }
+
+    if deps.servers.read().await.servers.contains_key(name) {
+        bot.send_message(message.chat.id, format!("Сервер '{}' уже существует", name))
+            .await?;
+        return Ok(());
+    }
+
+    bot.send_message(message.chat.id, "Введите адрес сервера")
+        .await?;
+
+    dialogue
+        .update(State::AddServerUrl {
+            name: name.to_string(),
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub(super) async fn callback_add_server_url(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+    name: String,
+) -> Result<(), BotError> {
+    let url = message_text(&message)?.trim();
+
+    if let Err(e) = validate_server_url(url) {
+        bot.send_message(message.chat.id, e).await?;
+        return Ok(());
+    }
+
+    add_server(&bot, message.chat.id, &deps, &name, url).await?;
+
+    dialogue.update(State::None).await?;
+
+    handle_start(bot, message, dialogue).await
+}
+
+async fn add_server(
+    bot: &Bot,
+    chat_id: ChatId,
+    deps: &DependenciesForDispatcher,
+    name: &str,
+    url: &str,
+) -> Result<(), BotError> {
+    if let Err(e) = validate_server_name(name).and_then(|_| validate_server_url(url)) {
+        bot.send_message(chat_id, e).await?;
+        return Ok(());
+    }
+
+    let mut registry = deps.servers.write().await;
+
+    if registry.servers.contains_key(name) {
+        bot.send_message(chat_id, format!("Сервер '{}' уже существует", name))
+            .await?;
+        return Ok(());
+    }
+
+    let mut config: toml::Table = read_to_struct(CFG_PATH).await?;
+
+    let mut servers = match config.remove("servers") {
+        Some(toml::Value::Table(servers)) => servers,
+        _ => toml::Table::new(),
+    };
+
+    servers.insert(name.to_string(), toml::Value::String(url.to_string()));
+    config.insert("servers".into(), toml::Value::Table(servers));
+
+    write_struct(CFG_PATH, &config).await?;
+
+    registry
+        .servers
+        .insert(name.to_string(), ServerConfig::from(url.to_string()));
+
+    for state in registry.chats.values_mut() {
+        state
+            .map
+            .insert(name.to_string(), ServerConfig::from(url.to_string()));
+    }
+
+    drop(registry);
+
+    tracing::info!(name, url, "server added");
+
+    bot.send_message(chat_id, format!("Сервер '{}' -> {} добавлен", name, url))
+        .await?;
+
+    Ok(())
+}
+
+fn validate_server_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Имя сервера не может быть пустым".into());
+    }
+
+    if !name.chars().all(char::is_alphanumeric) {
+        return Err("Имя сервера может содержать только буквы и цифры".into());
+    }
+
+    Ok(())
+}
+
+// Addresses are stored without a scheme, make_url adds it and the API prefix
+// itself. Servers added from the bot always use HTTPS.
+fn validate_server_url(url: &str) -> Result<(), String> {
+    let invalid = || Err(format!("Некорректный адрес сервера: '{}'", url));
+
+    let (host, port) = match url.split_once(':') {
+        Some((host, port)) => (host, Some(port)),
+        None => (url, None),
+    };
+
+    let host_ok = !host.is_empty()
+        && host.contains('.')
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+
+    let port_ok = port.is_none_or(|port| port.parse::<u16>().is_ok());
+
+    if host_ok && port_ok {
+        Ok(())
+    } else {
+        invalid()
+    }
+}
+
+pub(super) async fn handle_remove_server(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+) -> Result<(), BotError> {
+    let username = sender_username(&message)?;
+
+    if !is_admin(deps.admins_list.clone(), &username).await {
+        bot.send_message(message.chat.id, "Вы не находитесь в списке админов")
+            .await?;
+        return Ok(());
+    }
+
+    let mut names = deps
+        .servers
+        .read()
+        .await
+        .servers
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    names.sort();
+
+    // Same layout as switching: servers two per row, then "Отмена"
+    bot.send_message(message.chat.id, "Выберите сервер для удаления")
+        .reply_markup(build_switch_keyboard(&names))
+        .await?;
+
+    dialogue.update(State::RemoveServerSelect).await?;
+
+    Ok(())
+}
+
+pub(super) async fn callback_remove_server(
+    bot: Bot,
+    message: Message,
+    dialogue: MyDialogue,
+    deps: DependenciesForDispatcher,
+) -> Result<(), BotError> {
+    let name = message_text(&message)?;
+
+    // Keyboards sent before "Отмена" was added still carry "Назад"
+    if name != CANCEL && name != "Назад" {
+        remove_server(&bot, message.chat.id, &deps, name).await?;
+    }
+
+    dialogue.update(State::None).await?;
+
+    handle_start(bot, message, dialogue).await
+}
+
+async fn remove_server(
+    bot: &Bot,
+    chat_id: ChatId,
+    deps: &DependenciesForDispatcher,
+    name: &str,
+) -> Result<(), BotError> {
+    let mut registry = deps.servers.write().await;
+
+    if !registry.servers.contains_key(name) {
+        bot.send_message(chat_id, format!("Сервер '{}' не найден", name))
+            .await?;
+        return Ok(());
+    }
+
+    if registry.servers.len() == 1 {
+        bot.send_message(chat_id, "Нельзя удалить последний сервер")
+            .await?;
+        return Ok(());
+    }
+
+    let mut config: toml::Table = read_to_struct(CFG_PATH).await?;
+
+    if let Some(toml::Value::Table(servers)) = config.get_mut("servers") {
+        servers.remove(name);
+    }
+
+    write_struct(CFG_PATH, &config).await?;
+
+    let removed = registry.servers.remove(name);
+
+    if registry.current.as_deref() == Some(name) {
+        registry.current = None;
+    }
+
+    for state in registry.chats.values_mut() {
+        state.map.remove(name);
+
+        if state.current == name {
+            state.current = state.map.keys().min().cloned().unwrap_or_default();
+        }
+    }
+
+    let new_current = chat_server_state(&mut registry, chat_id).current.clone();
+    let state = registry.persisted();
+
+    // Another name may still point at the same server and share its pooled entry
+    let evict =
+        removed.filter(|removed| !registry.servers.values().any(|server| server == removed));
+
+    drop(registry);
+
+    write_struct(&deps.config.state_file, &state).await?;
+
+    if let Some(removed) = evict {
+        deps.pool.remove(&removed).await;
+    }
+
+    tracing::info!(name, "server removed");
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "Сервер '{}' удалён. Текущий сервер: '{}'",
+            name, new_current
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use tokio::sync::RwLock;
+use toml::{Table, Value};
+
+use crate::{
+    error::BotError,
+    shared::{read_to_struct, write_struct},
+};
+
+//
+
+// Telegram never sends the '@', but operators often type it in tg_cfg.toml
+pub fn normalize_username(s: &str) -> &str {
+    s.strip_prefix('@').unwrap_or(s)
+}
+
+fn same_user(a: &str, b: &str) -> bool {
+    normalize_username(a) == normalize_username(b)
+}
+
+//
+
+#[derive(Clone)]
+pub struct PersistentUserList {
+    users: Arc<RwLock<Vec<String>>>,
+    path: String,
+}
+
+impl PersistentUserList {
+    pub fn new<S: Into<String>>(users: Vec<String>, path: S) -> Self {
+        Self {
+            users: Arc::new(RwLock::new(users)),
+            path: path.into(),
+        }
+    }
+
+    pub async fn any<F: Fn(&str) -> bool>(&self, predicate: F) -> bool {
+        self.users.read().await.iter().any(|user| predicate(user))
+    }
+
+    pub async fn list(&self) -> Vec<String> {
+        self.users.read().await.clone()
+    }
+
+    pub async fn replace(&self, users: Vec<String>) {
+        *self.users.write().await = users;
+    }
+
+    pub async fn add(&self, username: &str) -> Result<(), BotError> {
+        let mut users = self.users.write().await;
+
+        if !users.iter().any(|user| same_user(user, username)) {
+            users.push(username.to_string());
+        }
+
+        // The file may already hold the user even when memory doesn't, e.g.
+        // after it was edited by hand since the last reload.
+        self.update_file(|accounts| {
+            if !accounts.iter().any(|account| same_user(account, username)) {
+                accounts.push(username.to_string());
+            }
+        })
+        .await
+    }
+
+    pub async fn remove(&self, username: &str) -> Result<bool, BotError> {
+        let mut users = self.users.write().await;
+
+        if !users.iter().any(|user| same_user(user, username)) {
+            return Ok(false);
+        }
+
+        users.retain(|user| !same_user(user, username));
+
+        self.update_file(|accounts| accounts.retain(|account| !same_user(account, username)))
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn update_file<F>(&self, update: F) -> Result<(), BotError>
+    where
+        F: FnOnce(&mut Vec<String>),
+    {
+        let mut config: Table = read_to_struct(&self.path).await?;
+
+        let mut accounts: Vec<String> = match config.remove("accounts") {
+            Some(value) => value.try_into()?,
+            None => Vec::new(),
+        };
+
+        update(&mut accounts);
+
+        config.insert("accounts".into(), Value::try_from(accounts)?);
+
+        write_struct(&self.path, &config).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn file_accounts(path: &str) -> Vec<String> {
+        let mut config: Table = read_to_struct(path).await.unwrap();
+        config.remove("accounts").unwrap().try_into().unwrap()
+    }
+
+    #[tokio::test]
+    async fn add_does_not_duplicate_a_user_already_in_the_file() {
+        let path = std::env::temp_dir()
+            .join(format!("iiko-bot-users-{}.toml", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+
+        tokio::fs::write(&path, "token = \"secret\"\naccounts = [\"alice\"]\n")
+            .await
+            .unwrap();
+
+        // Memory has fallen behind the file, as after a manual edit
+        let users = PersistentUserList::new(Vec::new(), &path);
+
+        users.add("alice").await.unwrap();
+        users.add("bob").await.unwrap();
+
+        assert_eq!(users.list().await, vec!["alice", "bob"]);
+        assert_eq!(file_accounts(&path).await, vec!["alice", "bob"]);
+
+        let config: Table = read_to_struct(&path).await.unwrap();
+        assert_eq!(config["token"].as_str(), Some("secret"));
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn add_and_remove_ignore_the_at_sign() {
+        let path = std::env::temp_dir()
+            .join(format!("iiko-bot-users-at-{}.toml", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+
+        tokio::fs::write(&path, "accounts = [\"@alice\"]\n")
+            .await
+            .unwrap();
+
+        let users = PersistentUserList::new(vec!["@alice".into()], &path);
+
+        users.add("alice").await.unwrap();
+
+        assert_eq!(users.list().await, vec!["@alice"]);
+        assert_eq!(file_accounts(&path).await, vec!["@alice"]);
+
+        assert!(users.remove("alice").await.unwrap());
+
+        assert!(users.list().await.is_empty());
+        assert!(file_accounts(&path).await.is_empty());
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}